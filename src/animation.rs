@@ -0,0 +1,335 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::cpu_render;
+use crate::deep_zoom::DeepZoom;
+use crate::fixed::Fixed;
+use crate::fractal::{FractalView, FRACTAL_KIND, FRACTAL_VIEW};
+use crate::location::Location;
+use crate::vk_offscreen::Offscreen;
+use crate::vk_present::{VkView, FRAGMENT_PUSH_CONSTANTS};
+use crate::vk_screenshot::save_screenshot;
+use crate::vk_utils::Vk;
+use crate::y4m::Y4mWriter;
+
+/// Animation to render instead of opening the interactive view, set from the command line.
+pub static ANIMATION: Lazy<Mutex<Option<Animation>>> = Lazy::new(|| {Mutex::new(None)} );
+
+pub struct Keyframe {
+    /// Seconds from the start of the animation.
+    pub time: f64,
+    pub location: Location,
+}
+
+/// Frames between keyframes, read from a file of `key = value` lines:
+///
+/// ```text
+/// size = 1920x1080
+/// fps = 30
+/// samples = 4
+/// output = frames
+/// keyframe = 0 bookmarks/1.location
+/// keyframe = 12.5 bookmarks/2.location
+/// ```
+///
+/// Keyframe locations are location files relative to the animation file, each read on top of
+/// the one before, and their times have to increase. An `output` ending in `.y4m` is written as
+/// one video file instead of a directory of frames.
+pub struct Animation {
+    pub size: [u32; 2],
+    pub fps: f64,
+    /// Samples per pixel, overriding the keyframes'.
+    pub samples: u32,
+    /// Directory the numbered frames are written to, or a `.y4m` file.
+    pub output: PathBuf,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Animation {
+    /// Reads an animation file, loading its keyframes on top of `start`.
+    pub fn load(path: &Path, start: &Location) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        let mut animation = Animation {
+            size: [1280, 720],
+            fps: 30.0,
+            samples: 1,
+            output: PathBuf::from("frames"),
+            keyframes: vec![],
+        };
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("{} line {}: expected `key = value`", path.display(), n + 1))?;
+            let (key, value) = (key.trim(), value.trim());
+            let invalid = || format!("{} line {}: invalid {key} `{value}`", path.display(), n + 1);
+
+            match key {
+                "size" => {
+                    let (w, h) = value.split_once('x').ok_or_else(invalid)?;
+                    animation.size = [w.parse().map_err(|_| invalid())?, h.parse().map_err(|_| invalid())?];
+                    if animation.size.contains(&0) {
+                        return Err(invalid());
+                    }
+                }
+                "fps" => {
+                    animation.fps = value.parse().map_err(|_| invalid())?;
+                    if !animation.fps.is_finite() || animation.fps <= 0.0 {
+                        return Err(invalid());
+                    }
+                }
+                "samples" => animation.samples = value.parse().map_err(|_| invalid())?,
+                "output" => animation.output = PathBuf::from(value),
+                "keyframe" => {
+                    let (time, file) = value.split_once(char::is_whitespace).ok_or_else(invalid)?;
+                    let time: f64 = time.parse().map_err(|_| invalid())?;
+                    if animation.keyframes.last().is_some_and(|last| last.time >= time) {
+                        return Err(invalid());
+                    }
+
+                    let previous = animation.keyframes.last().map_or(start, |last| &last.location);
+                    let location = previous.load(&dir.join(file.trim()))?;
+                    animation.keyframes.push(Keyframe { time, location });
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        if animation.keyframes.is_empty() {
+            return Err(format!("{}: no keyframes", path.display()));
+        }
+        Ok(animation)
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        (self.keyframes.last().unwrap().time * self.fps).floor() as u32 + 1
+    }
+
+    /// The location `time` seconds in. Zoom is interpolated in log space so it zooms at a
+    /// steady rate, the centre moves with ease in and out, the palette offset linearly.
+    /// Everything else is the previous keyframe's.
+    pub fn location_at(&self, time: f64) -> Location {
+        let next = self.keyframes.iter().position(|key| key.time > time);
+        let (a, b) = match next {
+            Some(0) => return self.keyframes[0].location.clone(),
+            Some(i) => (&self.keyframes[i - 1], &self.keyframes[i]),
+            None => return self.keyframes.last().unwrap().location.clone(),
+        };
+
+        let t = (time - a.time) / (b.time - a.time);
+        let eased = t * t * (3.0 - 2.0 * t);
+        let (from, to) = (&a.location, &b.location);
+
+        let mut location = from.clone();
+        location.zoom = from.zoom * (to.zoom / from.zoom).powf(t);
+        let limbs = DeepZoom::limbs(location.zoom);
+        location.center = [0, 1].map(|i| {
+            let (p, q) = (from.center[i].with_limbs(limbs), to.center[i].with_limbs(limbs));
+            p.add(&q.sub(&p).mul(&Fixed::from_f64(eased, limbs)))
+        });
+        location.palette_offset = from.palette_offset + (to.palette_offset - from.palette_offset) * t as f32;
+        location
+    }
+
+    fn frame_path(&self, frame: u32) -> PathBuf {
+        self.output.join(format!("frame_{frame:05}.png"))
+    }
+
+    fn create_output(&self) -> Output {
+        let result = if self.output.extension().is_some_and(|ext| ext == "y4m") {
+            Y4mWriter::create(&self.output, self.size, self.fps).map(Output::Video)
+        } else {
+            std::fs::create_dir_all(&self.output).map(|()| Output::Frames)
+        };
+        result.unwrap_or_else(|e| panic!("failed to create {}: {e}", self.output.display()))
+    }
+
+    /// Renders every frame offscreen. Goes through the same state as the interactive view, so
+    /// deep zoom keyframes work, and puts that state back afterwards.
+    pub fn render(&self, vk: &mut Vk, view: &mut VkView) {
+        let mut output = self.create_output();
+        let saved_pc = *FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+        let saved = Location::current(&saved_pc, &FRACTAL_VIEW.lock().unwrap(), *FRACTAL_KIND.lock().unwrap());
+
+        // rebuilt whenever the pipeline or the reference orbit it binds changes
+        let mut offscreen: Option<(Offscreen, _)> = None;
+
+        for frame in 0..self.frame_count() {
+            let location = self.location_at(frame as f64 / self.fps);
+            {
+                let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                pc.ires = self.size.map(|e| e as f32);
+                location.apply(&mut pc, &mut FRACTAL_VIEW.lock().unwrap(), &mut FRACTAL_KIND.lock().unwrap());
+                pc.samples = self.samples;
+            }
+            view.update(vk);
+
+            let key = (view.kind, view.precision, view.orbit_generation);
+            if offscreen.as_ref().map(|(_, k)| *k) != Some(key) {
+                offscreen = Some((Offscreen::new(vk, view, self.size, view.precision), key));
+            }
+            let img = offscreen.as_ref().unwrap().0.render(vk, view, view.push_constants);
+
+            match &mut output {
+                Output::Frames => {
+                    // encode off the render thread, but without letting the queue of frames grow
+                    let pool = &view.pool;
+                    while pool.queued_count() > pool.max_count() {
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                    }
+                    let path = self.frame_path(frame);
+                    pool.execute(move || save_screenshot(img, &path));
+                }
+                Output::Video(video) => self.write_video_frame(video, &img),
+            }
+            println!("animation frame {}/{}", frame + 1, self.frame_count());
+        }
+        view.pool.join();
+        self.finish(output);
+
+        let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+        *pc = saved_pc;
+        saved.apply(&mut pc, &mut FRACTAL_VIEW.lock().unwrap(), &mut FRACTAL_KIND.lock().unwrap());
+    }
+
+    /// Renders every frame with the CPU renderer, for machines without Vulkan, as for an sRGB
+    /// target. Like it, this stops at f64 precision, so keyframes in deep zoom are an error.
+    pub fn render_cpu(&self) -> Result<(), String> {
+        if let Some(key) = self.keyframes.iter().find(|key| key.location.deep()) {
+            return Err(format!("keyframe at {}s is in deep zoom, which --headless can not render", key.time));
+        }
+
+        let mut output = self.create_output();
+        let pool = threadpool::ThreadPool::new(12);
+
+        for frame in 0..self.frame_count() {
+            let location = self.location_at(frame as f64 / self.fps);
+            let mut pc = *FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+            pc.ires = self.size.map(|e| e as f32);
+            pc.samples = self.samples;
+            location.apply_params(&mut pc);
+            location.apply_view(&mut pc, &mut FractalView { cpos: [0.0, 0.0], zoom: 1.0 });
+
+            let img = cpu_render::render(&pc, location.kind, true, &pool);
+            match &mut output {
+                Output::Frames => save_screenshot(img, &self.frame_path(frame)),
+                Output::Video(video) => self.write_video_frame(video, &img),
+            }
+            println!("animation frame {}/{}", frame + 1, self.frame_count());
+        }
+        self.finish(output);
+        Ok(())
+    }
+
+    fn write_video_frame(&self, video: &mut Y4mWriter, img: &image::RgbaImage) {
+        video
+            .write_frame(img)
+            .unwrap_or_else(|e| panic!("failed to write to {}: {e}", self.output.display()));
+    }
+
+    fn finish(&self, output: Output) {
+        if let Output::Video(video) = output {
+            video
+                .finish()
+                .unwrap_or_else(|e| panic!("failed to write to {}: {e}", self.output.display()));
+            println!("saved animation to {}", self.output.display());
+        }
+    }
+}
+
+enum Output {
+    /// Numbered PNGs in the output directory.
+    Frames,
+    Video(Y4mWriter),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fractal::FractalKind;
+
+    fn keyframe(time: f64, center: [f64; 2], zoom: f64) -> Keyframe {
+        let pc = *FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+        let mut location = Location::current(&pc, &FRACTAL_VIEW.lock().unwrap(), FractalKind::Mandelbrot);
+        location.center = center.map(|c| Fixed::from_f64(c, 2));
+        location.zoom = zoom;
+        Keyframe { time, location }
+    }
+
+    fn animation() -> Animation {
+        Animation {
+            size: [64, 64],
+            fps: 10.0,
+            samples: 1,
+            output: PathBuf::from("frames"),
+            keyframes: vec![keyframe(1.0, [-1.0, 0.5], 1e-2), keyframe(3.0, [1.0, 0.5], 1e-6)],
+        }
+    }
+
+    fn center(location: &Location) -> [f64; 2] {
+        location.center.clone().map(|c| c.to_f64())
+    }
+
+    #[test]
+    fn location_at_holds_the_endpoints() {
+        let animation = animation();
+        for time in [0.0, 1.0] {
+            let location = animation.location_at(time);
+            assert_eq!(center(&location), [-1.0, 0.5]);
+            assert_eq!(location.zoom, 1e-2);
+        }
+        for time in [3.0, 4.0] {
+            let location = animation.location_at(time);
+            assert_eq!(center(&location), [1.0, 0.5]);
+            assert_eq!(location.zoom, 1e-6);
+        }
+    }
+
+    #[test]
+    fn location_at_zooms_in_log_space() {
+        let zoom = animation().location_at(2.0).zoom;
+        assert!((zoom / 1e-4 - 1.0).abs() < 1e-12, "{zoom}");
+    }
+
+    #[test]
+    fn location_at_eases_the_centre() {
+        let animation = animation();
+        // t = 0.25 eases to 0.15625, t = 0.5 to 0.5
+        assert_eq!(center(&animation.location_at(1.5)), [-0.6875, 0.5]);
+        assert_eq!(center(&animation.location_at(2.0)), [0.0, 0.5]);
+    }
+
+    fn load(name: &str, text: &str) -> Result<Animation, String> {
+        let path = std::env::temp_dir().join(format!("rvkp_{name}.animation"));
+        std::fs::write(&path, text).unwrap();
+        let start = keyframe(0.0, [0.0, 0.0], 1.0).location;
+        let result = Animation::load(&path, &start);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn unknown_keys_are_errors() {
+        assert!(load("unknown_key", "fsp = 30\n").is_err_and(|e| e.contains("invalid fsp")));
+    }
+
+    #[test]
+    fn fps_and_size_must_be_positive() {
+        for fps in ["0", "-30", "NaN", "inf"] {
+            let result = load("fps", &format!("fps = {fps}\n"));
+            assert!(result.is_err_and(|e| e.contains("invalid fps")), "{fps}");
+        }
+        for size in ["0x720", "1280x0"] {
+            let result = load("size", &format!("size = {size}\n"));
+            assert!(result.is_err_and(|e| e.contains("invalid size")), "{size}");
+        }
+    }
+}
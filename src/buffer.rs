@@ -1,12 +1,13 @@
 use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
 
-use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
-use vulkano::format::Format;
+use vulkano::image::{Image, ImageUsage};
 use vulkano::command_buffer::ClearColorImageInfo;
 use vulkano::format::ClearColorValue;
 
 use crate::vk_utils;
+use crate::vk_image::ImageDesc;
+use crate::vk_memory::{track_buffer, MemoryCategory};
 
 
 impl vk_utils::Vk {
@@ -18,7 +19,7 @@ impl vk_utils::Vk {
             <T as Iterator>::Item: Sync, 
             <T as Iterator>::Item: Send  
     {
-        Buffer::from_iter(
+        let buffer = Buffer::from_iter(
             self.mem_allocators.memory_allocator.clone(),
             BufferCreateInfo {
                 usage: BufferUsage::TRANSFER_DST,
@@ -30,12 +31,15 @@ impl vk_utils::Vk {
                 ..Default::default()
             },
             iter,
-        ).expect("failed to create buffer")
+        ).expect("failed to create buffer");
+
+        track_buffer(MemoryCategory::Staging, buffer.buffer());
+        buffer
     }
 
     pub fn vertex_buffer(&self, vec: Vec<crate::vk_pipeline::FVertex3d>)
     -> vulkano::buffer::Subbuffer<[crate::vk_pipeline::FVertex3d]> {
-        Buffer::from_iter(
+        let buffer = Buffer::from_iter(
             self.mem_allocators.memory_allocator.clone(),
             BufferCreateInfo {
                 usage: BufferUsage::VERTEX_BUFFER,
@@ -48,27 +52,29 @@ impl vk_utils::Vk {
             },
             vec,
         )
-        .expect("failed to create buffer")
+        .expect("failed to create buffer");
+
+        track_buffer(MemoryCategory::Vertex, buffer.buffer());
+        buffer
     }
 
+    /// RGBA8 render target; 3D if `dim[2] > 1`. See `ImageDesc` for anything else.
     pub fn image(&self, dim: [u32; 3]) -> std::sync::Arc<Image>  {
-        Image::new(
-            self.mem_allocators.memory_allocator.clone(),
-            ImageCreateInfo {
-                image_type: vulkano::image::ImageType::Dim2d,
-                format: Format::R8G8B8A8_UNORM,
-                extent: dim,
-                usage: ImageUsage::TRANSFER_DST 
+        let desc = if dim[2] > 1 {
+            ImageDesc::new_3d(dim)
+        } else {
+            ImageDesc::new_2d([dim[0], dim[1]])
+        };
+
+        self.create_image(
+            &desc.usage(
+                ImageUsage::TRANSFER_DST 
                     | ImageUsage::COLOR_ATTACHMENT
                     | ImageUsage::TRANSFER_SRC
-                    | ImageUsage::STORAGE,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
-                ..Default::default()
-            },
+                    | ImageUsage::STORAGE
+            ),
         )
         .unwrap()
+        .0
     }
 }
@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use image::RgbaImage;
+use once_cell::sync::Lazy;
+use vulkano::format::NumericFormat;
+
+use crate::cpu_render;
+use crate::deep_zoom::DEEP_ZOOM;
+use crate::vk_offscreen::Offscreen;
+use crate::vk_present::VkView;
+use crate::vk_screenshot::save_screenshot;
+use crate::vk_utils::Vk;
+
+/// Channels further apart than this count as a mismatch. The f32 shader and the f64 CPU
+/// renderer round differently, which moves smooth colouring by a step or two.
+const TOLERANCE: u8 = 8;
+
+/// Share of pixels allowed to mismatch: near the boundary rounding changes whether a point
+/// escapes at all.
+const MAX_MISMATCHED: f64 = 0.01;
+
+/// Directory `--compare` writes the GPU and CPU renders to, set from the command line.
+pub static COMPARE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| {Mutex::new(None)} );
+
+#[derive(Debug, PartialEq)]
+pub struct Difference {
+    /// Largest difference of any channel.
+    pub max: u8,
+    /// Pixels with a channel more than `tolerance` apart.
+    pub mismatched: usize,
+}
+
+/// Compares two images of the same size channel by channel, alpha included.
+pub fn compare(a: &RgbaImage, b: &RgbaImage, tolerance: u8) -> Difference {
+    assert_eq!(a.dimensions(), b.dimensions(), "compared images differ in size");
+
+    let mut difference = Difference { max: 0, mismatched: 0 };
+    for (pa, pb) in a.pixels().zip(b.pixels()) {
+        let max = pa.0.iter().zip(pb.0).map(|(ca, cb)| ca.abs_diff(cb)).max().unwrap();
+        difference.max = difference.max.max(max);
+        if max > tolerance {
+            difference.mismatched += 1;
+        }
+    }
+    difference
+}
+
+/// Renders the current view offscreen and with `cpu_render`, saves both to `dir` and reports
+/// whether they match. Adaptive supersampling is turned off, the CPU renderer supersamples
+/// every pixel.
+pub fn compare_gpu(vk: &mut Vk, view: &mut VkView, dir: &Path) -> bool {
+    if DEEP_ZOOM.lock().unwrap().is_some() {
+        println!("the CPU renderer does not support deep zoom, nothing to compare");
+        return false;
+    }
+
+    view.update(vk);
+    let mut pc = view.push_constants;
+    pc.adaptive = 0;
+    let extent = pc.ires.map(|e| e as u32);
+
+    let gpu = Offscreen::new(vk, view, extent, view.precision).render(vk, view, pc);
+    let srgb = vk.swapchain.clone().unwrap().image_format().numeric_format_color() == Some(NumericFormat::SRGB);
+    let cpu = cpu_render::render(&pc, view.kind, srgb, &view.pool);
+
+    let difference = compare(&gpu, &cpu, TOLERANCE);
+    let allowed = (MAX_MISMATCHED * (extent[0] * extent[1]) as f64) as usize;
+    println!(
+        "GPU ({:?}) vs CPU: {} of {} pixels off by more than {TOLERANCE}, at most {}",
+        view.precision,
+        difference.mismatched,
+        extent[0] * extent[1],
+        difference.max,
+    );
+
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        panic!("failed to create {}: {e}", dir.display());
+    }
+    save_screenshot(gpu, &dir.join("gpu.png"));
+    save_screenshot(cpu, &dir.join("cpu.png"));
+
+    difference.mismatched <= allowed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(pixels: &[[u8; 4]]) -> RgbaImage {
+        RgbaImage::from_raw(pixels.len() as u32, 1, pixels.concat()).unwrap()
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let a = image(&[[0, 0, 0, 255], [255, 128, 7, 255]]);
+        assert_eq!(compare(&a, &a, 0), Difference { max: 0, mismatched: 0 });
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let a = image(&[[10, 20, 30, 255], [200, 200, 200, 255]]);
+        let b = image(&[[18, 12, 30, 255], [200, 192, 208, 255]]);
+        assert_eq!(compare(&a, &b, 8), Difference { max: 8, mismatched: 0 });
+    }
+
+    #[test]
+    fn differences_beyond_tolerance_count_once_per_pixel() {
+        let a = image(&[[0, 0, 0, 255], [0, 0, 0, 255], [0, 0, 0, 255]]);
+        let b = image(&[[9, 9, 9, 255], [0, 0, 0, 255], [0, 0, 255, 255]]);
+        assert_eq!(compare(&a, &b, 8), Difference { max: 255, mismatched: 2 });
+    }
+
+    #[test]
+    fn target_encoding_only_changes_blended_colours() {
+        let mut pc = *crate::vk_present::FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+        pc.ires = [48.0, 40.0];
+        pc.trap_mode = crate::orbit_trap::TrapMode::Off as u32;
+        pc.distance_mode = crate::distance::DistanceMode::Off as u32;
+        pc.samples = 1;
+        let kind = crate::fractal::FractalKind::Mandelbrot;
+        let pool = threadpool::ThreadPool::new(4);
+
+        // single samples are palette colours, stored as they are on either target
+        let srgb = cpu_render::render(&pc, kind, true, &pool);
+        let unorm = cpu_render::render(&pc, kind, false, &pool);
+        assert_eq!(compare(&srgb, &unorm, 0).mismatched, 0);
+
+        // averages of samples are not
+        pc.samples = 4;
+        let srgb = cpu_render::render(&pc, kind, true, &pool);
+        let unorm = cpu_render::render(&pc, kind, false, &pool);
+        assert!(compare(&srgb, &unorm, 0).mismatched > 0);
+    }
+}
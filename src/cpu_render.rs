@@ -0,0 +1,356 @@
+use std::sync::mpsc;
+
+use image::RgbaImage;
+
+use crate::distance::DistanceMode;
+use crate::fractal::FractalKind;
+use crate::orbit_trap::TrapMode;
+use crate::palette::{Palette, PALETTES};
+use crate::sampling::SamplePattern;
+use crate::vk_present::fs;
+
+/// Rows each pool job renders.
+const BAND_HEIGHT: u32 = 16;
+
+/// Renders the view described by `pc` the way `vk_present::fs` does, but on the CPU and in f64
+/// throughout, so it matches the f32 shader up to its rounding and the wider variants closely.
+/// Deep zoom is not reproduced: `pc` has to describe a plain view (`reference_len` 0). Adaptive
+/// supersampling depends on the GPU's pixel quads, so when `samples` > 1 every pixel is
+/// supersampled.
+///
+/// `srgb` is whether the GPU would draw to an sRGB target. Its palette is then sRGB too, so the
+/// shader blends, shades and averages samples in linear space; on a UNORM target it works on
+/// the stored values instead.
+pub fn render(pc: &fs::PushConstantData, kind: FractalKind, srgb: bool, pool: &threadpool::ThreadPool) -> RgbaImage {
+    let [width, height] = [pc.ires[0] as u32, pc.ires[1] as u32];
+    let (sender, receiver) = mpsc::channel();
+
+    let bands = height.div_ceil(BAND_HEIGHT);
+    for band in 0..bands {
+        let pc = *pc;
+        let sender = sender.clone();
+        pool.execute(move || {
+            let rows = band * BAND_HEIGHT..((band + 1) * BAND_HEIGHT).min(height);
+            let mut texels = Vec::with_capacity(rows.len() * width as usize * 4);
+            for y in rows {
+                for x in 0..width {
+                    let [r, g, b] = pixel(&pc, kind, srgb, [x, y]);
+                    texels.extend_from_slice(&[r, g, b, 255]);
+                }
+            }
+            sender.send((band, texels)).unwrap();
+        });
+    }
+    drop(sender);
+
+    let mut img = RgbaImage::new(width, height);
+    let band_len = (BAND_HEIGHT * width * 4) as usize;
+    for (band, texels) in receiver {
+        let start = band as usize * band_len;
+        img.as_mut()[start..start + texels.len()].copy_from_slice(&texels);
+    }
+    img
+}
+
+fn pixel(pc: &fs::PushConstantData, kind: FractalKind, srgb: bool, [x, y]: [u32; 2]) -> [u8; 3] {
+    // `pos` as interpolated at the pixel centre from the fullscreen triangle
+    let pos = [
+        (x as f64 + 0.5) / pc.ires[0] as f64 * 2.0 - 1.0,
+        (y as f64 + 0.5) / pc.ires[1] as f64 * 2.0 - 1.0,
+    ];
+
+    if pc.samples <= 1 {
+        return colour(pc, kind, srgb, fractal(pc, kind, pixel_point(pc, pos)));
+    }
+
+    // the shader averages what it samples from the palette, the target encodes the average
+    let mut sum = [0.0; 3];
+    for s in 0..pc.samples {
+        let [dx, dy] = sample_offset(pc.sample_pattern, s, pc.samples, [x, y]);
+        let p = [
+            pos[0] + dx * 2.0 / pc.ires[0] as f64,
+            pos[1] + dy * 2.0 / pc.ires[1] as f64,
+        ];
+        let rgb = colour(pc, kind, srgb, fractal(pc, kind, pixel_point(pc, p)));
+        for c in 0..3 {
+            sum[c] += decode(rgb[c], srgb);
+        }
+    }
+    sum.map(|c| encode(c / pc.samples as f64, srgb))
+}
+
+/// Point of the complex plane at position `p` of the view, as `pixel_point` in the shader.
+fn pixel_point(pc: &fs::PushConstantData, p: [f64; 2]) -> [f64; 2] {
+    let zoom = pc.zoom as f64 + pc.zoom_lo as f64;
+    let [w, h] = [pc.ires[0] as f64, pc.ires[1] as f64];
+    let x = p[0] * zoom + 1.0 / w - (pc.cpos[0] as f64 + pc.cpos_lo[0] as f64);
+    let y = p[1] * zoom + 1.0 / h - (pc.cpos[1] as f64 + pc.cpos_lo[1] as f64);
+    [x, y * h / w]
+}
+
+fn sample_offset(pattern: u32, s: u32, n: u32, [x, y]: [u32; 2]) -> [f64; 2] {
+    let fract = |v: f64| v - v.floor();
+
+    if pattern == SamplePattern::BlueNoise as u32 {
+        // gl_FragCoord is the pixel centre
+        let noise = fract(52.9829189 * fract((x as f64 + 0.5) * 0.06711056 + (y as f64 + 0.5) * 0.00583715));
+        let k = (s + 1) as f64;
+        return [
+            fract(0.7548776662 * k + noise) - 0.5,
+            fract(0.5698402910 * k + noise) - 0.5,
+        ];
+    }
+
+    let side = (n as f64).sqrt().ceil() as u32;
+    let cell = [
+        ((s % side) as f64 + 0.5) / side as f64 - 0.5,
+        ((s / side) as f64 + 0.5) / side as f64 - 0.5,
+    ];
+    if pattern == SamplePattern::RotatedGrid as u32 {
+        let r = [0.8944272, 0.4472136];
+        return [
+            fract(cell[0] * r[0] - cell[1] * r[1] + 0.5) - 0.5,
+            fract(cell[0] * r[1] + cell[1] * r[0] + 0.5) - 0.5,
+        ];
+    }
+    cell
+}
+
+/// What colouring a pixel needs from its orbit.
+struct Orbit {
+    /// Smooth iteration count divided by `max_iterations`, 1.0 for points that never escape.
+    n: f64,
+    /// Closest the orbit came to the trap.
+    trap: f64,
+    /// z it escaped with, and its derivative for the distance estimate.
+    z: [f64; 2],
+    dz: [f64; 2],
+}
+
+fn fractal(pc: &fs::PushConstantData, kind: FractalKind, p: [f64; 2]) -> Orbit {
+    if kind == FractalKind::Newton {
+        return newton(pc, p);
+    }
+    if pc.julia != 0 {
+        escape_time(pc, kind, p, [pc.julia_c[0] as f64, pc.julia_c[1] as f64])
+    } else {
+        escape_time(pc, kind, [0.0, 0.0], p)
+    }
+}
+
+fn cmul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+fn cdiv(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    let d = b[0] * b[0] + b[1] * b[1];
+    [(a[0] * b[0] + a[1] * b[1]) / d, (a[1] * b[0] - a[0] * b[1]) / d]
+}
+
+fn cpow(z: [f64; 2], p: f64) -> [f64; 2] {
+    let r = (z[0] * z[0] + z[1] * z[1]).powf(p * 0.5);
+    let a = z[1].atan2(z[0]) * p;
+    [r * a.cos(), r * a.sin()]
+}
+
+fn step_z(pc: &fs::PushConstantData, kind: FractalKind, [x, y]: [f64; 2], c: [f64; 2]) -> [f64; 2] {
+    let [x, y] = match kind {
+        FractalKind::BurningShip => [x.abs(), y.abs()],
+        FractalKind::Tricorn => [x, -y],
+        FractalKind::Multibrot => {
+            let z = cpow([x, y], pc.power as f64);
+            return [z[0] + c[0], z[1] + c[1]];
+        }
+        _ => [x, y],
+    };
+
+    let mut re = x * x - y * y;
+    if kind == FractalKind::Celtic {
+        re = re.abs();
+    }
+    [re + c[0], 2.0 * x * y + c[1]]
+}
+
+/// Whether `kind` has a distance estimate, see `DistanceMode`.
+fn has_distance(kind: FractalKind) -> bool {
+    matches!(kind, FractalKind::Mandelbrot | FractalKind::Multibrot)
+}
+
+fn escape_time(pc: &fs::PushConstantData, kind: FractalKind, mut z: [f64; 2], c: [f64; 2]) -> Orbit {
+    let distance = has_distance(kind) && pc.distance_mode != DistanceMode::Off as u32;
+    let mut r2 = pc.escape_radius as f64 * pc.escape_radius as f64;
+    if distance {
+        // the estimate needs z well past the escape radius, as in the shader
+        r2 = r2.max(1e6);
+    }
+    let mut trap = Trap::new(pc);
+    let mut dz = if pc.julia != 0 { [1.0, 0.0] } else { [0.0, 0.0] };
+
+    let mut i = 0;
+    while i < pc.max_iterations {
+        if distance {
+            // dz' = 2 z dz + 1, by c, or by the starting point for Julia sets
+            dz = match kind {
+                FractalKind::Multibrot => {
+                    let d = cmul(cpow(z, pc.power as f64 - 1.0), dz);
+                    [pc.power as f64 * d[0], pc.power as f64 * d[1]]
+                }
+                _ => {
+                    let d = cmul(z, dz);
+                    [2.0 * d[0], 2.0 * d[1]]
+                }
+            };
+            if pc.julia == 0 {
+                dz[0] += 1.0;
+            }
+        }
+        z = step_z(pc, kind, z, c);
+        trap.visit(z);
+        if z[0] * z[0] + z[1] * z[1] > r2 {
+            break;
+        }
+        i += 1;
+    }
+
+    if i == pc.max_iterations {
+        return Orbit { n: 1.0, trap: trap.min, z, dz };
+    }
+
+    let degree = if kind == FractalKind::Multibrot { pc.power as f64 } else { 2.0 };
+    let nu = i as f64 + 1.0 - ((z[0] * z[0] + z[1] * z[1]).ln() / r2.ln()).ln() / degree.ln();
+    Orbit { n: nu / pc.max_iterations as f64, trap: trap.min, z, dz }
+}
+
+fn newton(pc: &fs::PushConstantData, mut z: [f64; 2]) -> Orbit {
+    let n = (pc.power as f64).round().max(2.0);
+    let tolerance = 1e-6;
+    let mut d2 = 1.0;
+    let mut trap = Trap::new(pc);
+
+    let mut i = 0;
+    while i < pc.max_iterations {
+        let zn1 = cpow(z, n - 1.0);
+        let w = cmul(zn1, z);
+        let step = cdiv([w[0] - 1.0, w[1]], [n * zn1[0], n * zn1[1]]);
+        z = [z[0] - step[0], z[1] - step[1]];
+        trap.visit(z);
+
+        d2 = step[0] * step[0] + step[1] * step[1];
+        if d2 < tolerance {
+            break;
+        }
+        i += 1;
+    }
+
+    // no distance estimate, Newton's method is not an escape
+    let dz = [0.0, 0.0];
+    if i == pc.max_iterations {
+        return Orbit { n: 1.0, trap: trap.min, z, dz };
+    }
+
+    let nu = i as f64 + 1.0 - (d2.ln() / f64::ln(tolerance)).log2();
+    let n = nu.clamp(0.0, pc.max_iterations as f64 - 1.0) / pc.max_iterations as f64;
+    Orbit { n, trap: trap.min, z, dz }
+}
+
+/// Closest approach of an orbit to the trap.
+struct Trap {
+    mode: TrapMode,
+    pos: [f64; 2],
+    size: f64,
+    min: f64,
+}
+
+impl Trap {
+    fn new(pc: &fs::PushConstantData) -> Self {
+        Self {
+            mode: TrapMode::ALL[pc.trap_mode as usize],
+            pos: pc.trap_pos.map(|p| p as f64),
+            size: pc.trap_size as f64,
+            min: 1e20,
+        }
+    }
+
+    fn visit(&mut self, z: [f64; 2]) {
+        if self.mode != TrapMode::Off {
+            self.min = self.min.min(self.mode.distance(z, self.pos, self.size));
+        }
+    }
+}
+
+fn colour(pc: &fs::PushConstantData, kind: FractalKind, srgb: bool, orbit: Orbit) -> [u8; 3] {
+    let rgb = base_colour(pc, srgb, &orbit);
+    let mode = DistanceMode::ALL[pc.distance_mode as usize];
+    if !has_distance(kind) || mode == DistanceMode::Off {
+        return rgb;
+    }
+
+    // distance from the boundary in pixels, |z| ln|z| / |dz|
+    let r = orbit.z[0].hypot(orbit.z[1]);
+    let pixel = 2.0 * (pc.zoom as f64 + pc.zoom_lo as f64) / pc.ires[0] as f64;
+    let distance = r * r.ln() / orbit.dz[0].hypot(orbit.dz[1]) / pixel;
+
+    // shaded in the space the shader sees the palette in
+    let shade = |f: f64| rgb.map(|c| encode(decode(c, srgb) * f, srgb));
+    match mode {
+        DistanceMode::Boundary => {
+            let d = if orbit.n >= 1.0 { 0.0 } else { distance / pc.distance_width as f64 };
+            [encode(d.clamp(0.0, 1.0).sqrt(), srgb); 3]
+        }
+        _ if orbit.n >= 1.0 => rgb,
+        DistanceMode::Outline => shade((distance - pc.distance_width as f64).clamp(0.0, 1.0)),
+        _ => {
+            let u = cdiv(orbit.z, orbit.dz);
+            let len = u[0].hypot(u[1]);
+            let angle = pc.light_angle as f64;
+            let h = pc.light_height as f64;
+            let lit = (u[0] / len * angle.cos() + u[1] / len * angle.sin() + h) / (1.0 + h);
+            shade(lit.max(0.0))
+        }
+    }
+}
+
+/// Escape time or orbit trap colouring.
+fn base_colour(pc: &fs::PushConstantData, srgb: bool, orbit: &Orbit) -> [u8; 3] {
+    let palette: &Palette = &PALETTES[pc.palette_index as usize];
+    let n = orbit.n;
+
+    if pc.trap_mode != TrapMode::Off as u32 {
+        // palette by how close the orbit came, fading out with distance, in the space the
+        // shader blends in
+        let t = orbit.trap / pc.trap_size as f64;
+        let rgb = palette.sample((t + pc.palette_offset as f64) as f32);
+        return rgb.map(|c| encode(decode(c, srgb) / (1.0 + t), srgb));
+    }
+
+    if n >= 1.0 {
+        return [0, 0, 0];
+    }
+
+    let t = n * pc.max_iterations as f64 * pc.palette_scale as f64 + pc.palette_offset as f64;
+    palette.sample(t as f32)
+}
+
+/// Palette value as the shader reads it, linear when sampled from an sRGB texture.
+fn decode(c: u8, srgb: bool) -> f64 {
+    let c = c as f64 / 255.0;
+    if !srgb {
+        c
+    } else if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Shader output as the target stores it.
+fn encode(c: f64, srgb: bool) -> u8 {
+    let c = if !srgb {
+        c
+    } else if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
@@ -0,0 +1,220 @@
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::fixed::Fixed;
+use crate::fractal::FractalView;
+use crate::vk_present::fs;
+
+/// Deep zoom view, `Some` while the mode is on. The centre is kept at whatever precision the
+/// zoom needs; the GPU only iterates each pixel's offset from the reference orbit of the centre.
+pub static DEEP_ZOOM: Lazy<Mutex<Option<DeepZoom>>> = Lazy::new(|| {Mutex::new(None)} );
+
+/// Zooms past which f64 can no longer place pixels, the Mandelbrot switches to deep zoom here.
+pub const DEEP_ZOOM_BELOW: f64 = 1e-11;
+
+pub struct DeepZoom {
+    pub center: [Fixed; 2],
+    pub zoom: f64,
+    /// Z_0, Z_1, ... of the centre, up to and including the first escaped value.
+    pub orbit: Vec<[f32; 2]>,
+    /// Bumped whenever `orbit` is recomputed so the renderer knows to upload it again.
+    pub generation: u64,
+    orbit_inputs: Option<OrbitInputs>,
+}
+
+// everything the reference orbit depends on
+#[derive(Clone, PartialEq)]
+struct OrbitInputs {
+    center: [Fixed; 2],
+    max_iterations: u32,
+    escape_radius: f32,
+    julia: Option<[f32; 2]>,
+}
+
+impl DeepZoom {
+    pub fn new([x, y]: [f64; 2], zoom: f64) -> Self {
+        let limbs = Self::limbs(zoom);
+        Self::at([Fixed::from_f64(x, limbs), Fixed::from_f64(y, limbs)], zoom)
+    }
+
+    /// Deep zoom centred on `center`, given at least as precisely as `zoom` needs.
+    pub fn at(center: [Fixed; 2], zoom: f64) -> Self {
+        let mut deep = Self {
+            center: [Fixed::zero(1), Fixed::zero(1)],
+            zoom,
+            orbit: vec![],
+            generation: 0,
+            orbit_inputs: None,
+        };
+        deep.move_to(&center, zoom);
+        deep
+    }
+
+    /// Jumps to `center` and `zoom`, keeping the orbit if the centre did not change.
+    pub fn move_to(&mut self, center: &[Fixed; 2], zoom: f64) {
+        let limbs = Self::limbs(zoom);
+        self.center = [center[0].with_limbs(limbs), center[1].with_limbs(limbs)];
+        self.zoom = zoom;
+    }
+
+    /// Limbs needed to tell pixels apart at `zoom`, with a limb to spare.
+    pub fn limbs(zoom: f64) -> usize {
+        ((-zoom.log2()).max(0.0) / 64.0).ceil() as usize + 2
+    }
+
+    /// Moves the centre by `[dx, dy]` in the complex plane.
+    pub fn pan(&mut self, [dx, dy]: [f64; 2]) {
+        let limbs = Self::limbs(self.zoom);
+        self.center = [
+            self.center[0].with_limbs(limbs).add(&Fixed::from_f64(dx, limbs)),
+            self.center[1].with_limbs(limbs).add(&Fixed::from_f64(dy, limbs)),
+        ];
+    }
+
+    pub fn zoom_by(&mut self, factor: f64) {
+        self.zoom *= factor;
+        let limbs = Self::limbs(self.zoom);
+        self.center = [self.center[0].with_limbs(limbs), self.center[1].with_limbs(limbs)];
+    }
+
+    /// Recomputes the reference orbit if anything it depends on changed, and writes the zoom,
+    /// split into an f32 mantissa and a power of two, and the orbit length into `pc`. The view
+    /// is centred on the reference, so `cpos` is zeroed.
+    pub fn update(&mut self, pc: &mut fs::PushConstantData) {
+        let inputs = OrbitInputs {
+            center: self.center.clone(),
+            max_iterations: pc.max_iterations,
+            escape_radius: pc.escape_radius,
+            julia: (pc.julia != 0).then_some(pc.julia_c),
+        };
+
+        if self.orbit_inputs.as_ref() != Some(&inputs) {
+            self.orbit = reference_orbit(&inputs);
+            self.orbit_inputs = Some(inputs);
+            self.generation += 1;
+        }
+
+        let exponent = self.zoom.log2().floor();
+        pc.zoom = (self.zoom / exponent.exp2()) as f32;
+        pc.scale_exp = exponent as i32;
+        pc.reference_len = self.orbit.len() as u32;
+        pc.cpos = [0.0, 0.0];
+        pc.cpos_lo = [0.0, 0.0];
+    }
+
+    /// Points `view` at the deep zoom centre, as far as f64 can.
+    pub fn sync_view(&self, view: &mut FractalView, ires: [f32; 2]) {
+        view.zoom = self.zoom;
+        view.set_center(ires, [self.center[0].to_f64(), self.center[1].to_f64()]);
+    }
+}
+
+/// `FractalView::tile` for push constants set by `DeepZoom::update`. The tile keeps the
+/// reference orbit, `cpos` offsets its centre from it in units of `2^scale_exp`.
+pub fn tile(pc: &fs::PushConstantData, ires: [f32; 2], origin: [u32; 2], size: [u32; 2]) -> fs::PushConstantData {
+    let zoom = pc.zoom as f64;
+    let width = ires[0] as f64;
+    let offset = [0, 1].map(|a| zoom * (size[a] as f64 + 2.0 * origin[a] as f64 - ires[a] as f64) / width);
+
+    fs::PushConstantData {
+        zoom: (zoom * size[0] as f64 / width) as f32,
+        cpos: offset.map(|o| o as f32),
+        ..*pc
+    }
+}
+
+fn reference_orbit(inputs: &OrbitInputs) -> Vec<[f32; 2]> {
+    let limbs = inputs.center[0].limbs.len();
+    let (mut z, c) = match inputs.julia {
+        Some([x, y]) => (
+            inputs.center.clone(),
+            [Fixed::from_f64(x as f64, limbs), Fixed::from_f64(y as f64, limbs)],
+        ),
+        None => ([Fixed::zero(limbs), Fixed::zero(limbs)], inputs.center.clone()),
+    };
+    let escape = inputs.escape_radius as f64 * inputs.escape_radius as f64;
+
+    let mut orbit = Vec::with_capacity(inputs.max_iterations as usize + 1);
+    orbit.push([z[0].to_f64() as f32, z[1].to_f64() as f32]);
+
+    for _ in 0..inputs.max_iterations {
+        let xx = z[0].mul(&z[0]);
+        let yy = z[1].mul(&z[1]);
+        let xy = z[0].mul(&z[1]);
+        z = [xx.sub(&yy).add(&c[0]), xy.mul_u64(2).add(&c[1])];
+
+        let [x, y] = [z[0].to_f64(), z[1].to_f64()];
+        orbit.push([x as f32, y as f32]);
+        if x * x + y * y > escape {
+            break;
+        }
+    }
+
+    orbit
+}
+
+/// Switches deep zoom on, starting from `view`, or off, moving `view` to where deep zoom was.
+pub fn toggle_deep_zoom(pc: &mut fs::PushConstantData, view: &mut FractalView) {
+    let mut deep_zoom = DEEP_ZOOM.lock().unwrap();
+
+    match deep_zoom.take() {
+        None => {
+            let mut deep = DeepZoom::new(view.center(pc.ires), view.zoom);
+            deep.update(pc);
+            *deep_zoom = Some(deep);
+            println!("deep zoom on");
+        }
+        Some(deep) => {
+            deep.sync_view(view, pc.ires);
+            view.apply(pc);
+            pc.scale_exp = 0;
+            pc.reference_len = 0;
+            println!("deep zoom off");
+        }
+    }
+}
+
+/// Leaves deep zoom, if it is on.
+pub fn leave_deep_zoom(pc: &mut fs::PushConstantData, view: &mut FractalView) {
+    if DEEP_ZOOM.lock().unwrap().is_some() {
+        toggle_deep_zoom(pc, view);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vk_present::FRAGMENT_PUSH_CONSTANTS;
+
+    /// Offset of the centre of `pixel` from the reference, in units of `2^scale_exp`, as the
+    /// shader passes it to `perturbed`.
+    fn offset(pc: &fs::PushConstantData, pixel: [u32; 2]) -> [f64; 2] {
+        let p = [0, 1].map(|a| (pixel[a] as f64 + 0.5) / pc.ires[a] as f64 * 2.0 - 1.0);
+        let zoom = pc.zoom as f64;
+        [
+            p[0] * zoom + pc.cpos[0] as f64,
+            p[1] * pc.ires[1] as f64 / pc.ires[0] as f64 * zoom + pc.cpos[1] as f64,
+        ]
+    }
+
+    #[test]
+    fn tiles_line_up_with_the_full_view() {
+        let mut pc = *FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+        pc.ires = [300.0, 200.0];
+        DeepZoom::new([-0.75, 0.1], 1e-20).update(&mut pc);
+        let side = 128;
+
+        for origin in [[0, 0], [128, 0], [256, 128]] {
+            let mut tile_pc = tile(&pc, pc.ires, origin, [side, side]);
+            tile_pc.ires = [side as f32; 2];
+            for pixel in [[0, 0], [5, 17], [127, 127]] {
+                let full = offset(&pc, [origin[0] + pixel[0], origin[1] + pixel[1]]);
+                let tiled = offset(&tile_pc, pixel);
+                for a in 0..2 {
+                    assert!((full[a] - tiled[a]).abs() < 1e-5, "{origin:?} {pixel:?}");
+                }
+            }
+        }
+    }
+}
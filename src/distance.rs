@@ -0,0 +1,23 @@
+/// What the exterior distance estimate is used for, indexed by the `distance_mode` push
+/// constant. The estimate comes from the derivative of the orbit, so only the Mandelbrot and
+/// Multibrot sets and their Julia sets have one, the other kinds ignore this.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistanceMode {
+    /// Plain escape time colouring.
+    Off,
+    /// White fading to black at the boundary, thin filaments stay visible at any zoom.
+    Boundary,
+    /// The usual colours with the boundary drawn `distance_width` pixels thick.
+    Outline,
+    /// The usual colours shaded as a relief lit from `light_angle`.
+    Relief,
+}
+
+impl DistanceMode {
+    pub const ALL: [DistanceMode; 4] = [
+        DistanceMode::Off,
+        DistanceMode::Boundary,
+        DistanceMode::Outline,
+        DistanceMode::Relief,
+    ];
+}
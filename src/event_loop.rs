@@ -21,20 +21,53 @@ use vulkano::sync::future::FenceSignalFuture;
 use crate::vk_pipeline::vert;
 use crate::vk_present::{VkPresenter, VkView};
 use crate::vk_present::{FRAGMENT_PUSH_CONSTANTS, WINDOW_RESIZED};
+use crate::vk_memory::MEMORY_TRACKER;
+use crate::palette::PALETTES;
+use crate::sampling::{SamplePattern, SAMPLE_COUNTS};
+use crate::orbit_trap::TrapMode;
+use crate::distance::DistanceMode;
+use crate::poster::export_poster;
+use crate::gif_export::export_gif;
+use crate::location::{slot_path, Location};
+use crate::animation::ANIMATION;
+use crate::compare::{compare_gpu, COMPARE};
+use crate::fractal::{FractalKind, FractalView, FRACTAL_KIND, FRACTAL_VIEW};
+use crate::deep_zoom::{leave_deep_zoom, toggle_deep_zoom, DEEP_ZOOM, DEEP_ZOOM_BELOW};
 pub fn run() {
     let event_loop = EventLoop::new();
     let mut vk = Arc::new(Mutex::new(crate::vk_utils::Vk::new(&event_loop)));
 
-    let window = Arc::new(WindowBuilder::new().build(&event_loop).unwrap()); 
+    // animations and comparisons render offscreen, the window is only there for the swapchain format
+    let window = Arc::new(WindowBuilder::new()
+        .with_visible(ANIMATION.lock().unwrap().is_none() && COMPARE.lock().unwrap().is_none())
+        .build(&event_loop).unwrap()); 
     window.set_title("VULKAN");
 
-    let mut view = Arc::new(Mutex::new(VkView::new(&mut vk.clone().lock().unwrap(), window.clone())));
+    let pool = threadpool::ThreadPool::new(12);
+
+    let mut view = Arc::new(Mutex::new(VkView::new(&mut vk.clone().lock().unwrap(), window.clone(), pool.clone())));
     let mut presenter = VkPresenter::new(&mut vk.clone().lock().unwrap());
+
+    if let Some(animation) = ANIMATION.lock().unwrap().take() {
+        animation.render(&mut vk.clone().lock().unwrap(), &mut view.clone().lock().unwrap());
+        return;
+    }
+    if let Some(dir) = COMPARE.lock().unwrap().take() {
+        if !compare_gpu(&mut vk.clone().lock().unwrap(), &mut view.clone().lock().unwrap(), &dir) {
+            std::process::exit(1);
+        }
+        return;
+    }
     let mut frame_id = 0;
 
     let mut bool_key = [false; 6];
-
-    let pool = threadpool::ThreadPool::new(12);
+    let mut cycle_palette = false;
+    let mut cursor = [0.0f32; 2];
+    let mut modifiers = ModifiersState::empty();
+    // Mandelbrot view to return to when leaving Julia mode
+    let mut mandelbrot_view = *FRACTAL_VIEW.lock().unwrap();
+    // zoom of the last frame, to switch deep zoom only when crossing DEEP_ZOOM_BELOW
+    let mut previous_zoom = FRACTAL_VIEW.lock().unwrap().zoom;
 
     event_loop.run(move |event, _, control_flow| {
         match event {
@@ -69,6 +102,135 @@ pub fn run() {
                         if virtual_keycode == VirtualKeyCode::K {
                             bool_key[5] = true;
                         }
+                        if virtual_keycode == VirtualKeyCode::F12 {
+                            presenter.request_screenshot();
+                        }
+                        // Ctrl + digit saves the view to that bookmark slot, the digit alone jumps to it
+                        if let Some(slot) = digit(virtual_keycode) {
+                            let path = slot_path(slot);
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            let mut view = FRACTAL_VIEW.lock().unwrap();
+                            let mut kind = FRACTAL_KIND.lock().unwrap();
+                            let current = Location::current(&pc, &view, *kind);
+
+                            if modifiers.ctrl() {
+                                match current.save(&path) {
+                                    Ok(()) => println!("saved bookmark {slot} to {}", path.display()),
+                                    Err(e) => println!("failed to save bookmark {slot} to {}: {e}", path.display()),
+                                }
+                            } else {
+                                match current.load(&path) {
+                                    Ok(location) => {
+                                        location.apply(&mut pc, &mut view, &mut kind);
+                                        println!("jumped to bookmark {slot}");
+                                    }
+                                    Err(e) => println!("failed to load bookmark {e}"),
+                                }
+                            }
+                        }
+                        if virtual_keycode == VirtualKeyCode::O {
+                            export_poster(&vk.clone().lock().unwrap(), &view.clone().lock().unwrap());
+                        }
+                        if virtual_keycode == VirtualKeyCode::L {
+                            export_gif(&vk.clone().lock().unwrap(), &view.clone().lock().unwrap());
+                        }
+
+                        // iteration count and escape radius step once per press
+                        if matches!(virtual_keycode, VirtualKeyCode::Up | VirtualKeyCode::Down | VirtualKeyCode::Left | VirtualKeyCode::Right) {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            match virtual_keycode {
+                                VirtualKeyCode::Up => pc.max_iterations = (pc.max_iterations as f32 * 1.25).ceil() as u32,
+                                VirtualKeyCode::Down => pc.max_iterations = (pc.max_iterations as f32 / 1.25).floor().max(1.0) as u32,
+                                VirtualKeyCode::Right => pc.escape_radius *= 2.0,
+                                _ => pc.escape_radius = (pc.escape_radius / 2.0).max(2.0),
+                            }
+                            println!("max_iterations {} escape_radius {}", pc.max_iterations, pc.escape_radius);
+                        }
+
+                        if virtual_keycode == VirtualKeyCode::P {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            pc.palette_index = (pc.palette_index + 1) % PALETTES.len() as u32;
+                            println!("palette {}", PALETTES[pc.palette_index as usize].name);
+                        }
+                        if virtual_keycode == VirtualKeyCode::J {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            let c = pc.julia_c;
+                            toggle_julia(&mut pc, &mut FRACTAL_VIEW.lock().unwrap(), &mut mandelbrot_view, c);
+                        }
+                        if virtual_keycode == VirtualKeyCode::V {
+                            if *FRACTAL_KIND.lock().unwrap() == FractalKind::Mandelbrot {
+                                toggle_deep_zoom(&mut FRAGMENT_PUSH_CONSTANTS.lock().unwrap(), &mut FRACTAL_VIEW.lock().unwrap());
+                            } else {
+                                println!("deep zoom only supports the Mandelbrot and its Julia sets");
+                            }
+                        }
+                        if virtual_keycode == VirtualKeyCode::Tab {
+                            // the other kinds have no perturbation formula
+                            leave_deep_zoom(&mut FRAGMENT_PUSH_CONSTANTS.lock().unwrap(), &mut FRACTAL_VIEW.lock().unwrap());
+                            let mut kind = FRACTAL_KIND.lock().unwrap();
+                            *kind = kind.next();
+                            println!("fractal {:?}", *kind);
+                        }
+                        if matches!(virtual_keycode, VirtualKeyCode::LBracket | VirtualKeyCode::RBracket) {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            let step = if virtual_keycode == VirtualKeyCode::RBracket { 0.1 } else { -0.1 };
+                            pc.power = (pc.power + step).max(1.1);
+                            println!("power {:.1}", pc.power);
+                        }
+                        if virtual_keycode == VirtualKeyCode::C {
+                            cycle_palette = !cycle_palette;
+                        }
+                        if virtual_keycode == VirtualKeyCode::X {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            let i = SAMPLE_COUNTS.iter().position(|n| *n == pc.samples).unwrap_or(0);
+                            pc.samples = SAMPLE_COUNTS[(i + 1) % SAMPLE_COUNTS.len()];
+                            println!("samples per pixel {}", pc.samples);
+                        }
+                        if virtual_keycode == VirtualKeyCode::Z {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            pc.sample_pattern = (pc.sample_pattern + 1) % SamplePattern::ALL.len() as u32;
+                            println!("sample pattern {:?}", SamplePattern::ALL[pc.sample_pattern as usize]);
+                        }
+                        if virtual_keycode == VirtualKeyCode::G {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            pc.trap_mode = (pc.trap_mode + 1) % TrapMode::ALL.len() as u32;
+                            println!("orbit trap {:?}", TrapMode::ALL[pc.trap_mode as usize]);
+                        }
+                        if matches!(virtual_keycode, VirtualKeyCode::Comma | VirtualKeyCode::Period) {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            pc.trap_size *= if virtual_keycode == VirtualKeyCode::Period { 1.5 } else { 1.0 / 1.5 };
+                            println!("trap size {}", pc.trap_size);
+                        }
+                        if virtual_keycode == VirtualKeyCode::E {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            pc.distance_mode = (pc.distance_mode + 1) % DistanceMode::ALL.len() as u32;
+                            println!("distance estimation {:?}", DistanceMode::ALL[pc.distance_mode as usize]);
+                        }
+                        if matches!(virtual_keycode, VirtualKeyCode::Minus | VirtualKeyCode::Equals) {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            pc.distance_width *= if virtual_keycode == VirtualKeyCode::Equals { 1.5 } else { 1.0 / 1.5 };
+                            println!("outline width {} pixels", pc.distance_width);
+                        }
+                        // the light goes round in 15 degree steps, Shift turns it back
+                        if virtual_keycode == VirtualKeyCode::R {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            let step = if modifiers.shift() { -15f32 } else { 15.0 };
+                            pc.light_angle = (pc.light_angle + step.to_radians()).rem_euclid(std::f32::consts::TAU);
+                            println!("light angle {:.0} degrees", pc.light_angle.to_degrees());
+                        }
+                        if virtual_keycode == VirtualKeyCode::H {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            pc.equalize = 1 - pc.equalize;
+                            println!("histogram equalization {}", if pc.equalize != 0 { "on" } else { "off" });
+                            if pc.samples > 1 {
+                                println!("histogram equalization needs one sample per pixel");
+                            }
+                        }
+                        if virtual_keycode == VirtualKeyCode::Q {
+                            let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                            pc.adaptive = 1 - pc.adaptive;
+                            println!("adaptive supersampling {}", if pc.adaptive != 0 { "on" } else { "off" });
+                        }
                     },
 
                     ElementState::Released => {
@@ -94,6 +256,37 @@ pub fn run() {
                 }
             }
 
+            Event::WindowEvent {
+                event: WindowEvent::ModifiersChanged(state),
+                ..
+            } => {
+                modifiers = state;
+            },
+
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                cursor = [position.x as f32, position.y as f32];
+            },
+
+            // clicking a point of the Mandelbrot set shows the Julia set of that point
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. },
+                ..
+            } => {
+                let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                let mut fractal_view = FRACTAL_VIEW.lock().unwrap();
+                let [x, y] = fractal_view.pixel_to_complex(pc.ires, cursor);
+                // Shift + click places the orbit trap, a click into the Mandelbrot set picks a Julia set
+                if modifiers.shift() {
+                    pc.trap_pos = [x as f32, y as f32];
+                    println!("trap at {} {:+}i", x, y);
+                } else if pc.julia == 0 {
+                    toggle_julia(&mut pc, &mut fractal_view, &mut mandelbrot_view, [x as f32, y as f32]);
+                }
+            },
+
             Event::WindowEvent {
                 event: WindowEvent::Resized(_),
                 ..
@@ -109,35 +302,91 @@ pub fn run() {
                 let vk_c = vk.clone();
                 let window_c = window.clone();
 
-                let zoom = FRAGMENT_PUSH_CONSTANTS.lock().unwrap().zoom;
+                let mut pc = FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+                let mut fractal_view = FRACTAL_VIEW.lock().unwrap();
 
-                if bool_key[0] {
-                    FRAGMENT_PUSH_CONSTANTS.lock().unwrap().cpos[1] += 0.001 * zoom;
-                }
-                if bool_key[1] {
-                    FRAGMENT_PUSH_CONSTANTS.lock().unwrap().cpos[0] += 0.001 * zoom;
-                }
-                if bool_key[2] {
-                    FRAGMENT_PUSH_CONSTANTS.lock().unwrap().cpos[1] -= 0.001 * zoom;
+                if let Some(deep) = DEEP_ZOOM.lock().unwrap().as_mut() {
+                    // same directions as the cpos movement below, but on the high precision centre
+                    let step = 0.001 * deep.zoom;
+                    let aspect = (pc.ires[1] / pc.ires[0]) as f64;
+
+                    if bool_key[0] {
+                        deep.pan([0.0, -step * aspect]);
+                    }
+                    if bool_key[1] {
+                        deep.pan([-step, 0.0]);
+                    }
+                    if bool_key[2] {
+                        deep.pan([0.0, step * aspect]);
+                    }
+                    if bool_key[3] {
+                        deep.pan([step, 0.0]);
+                    }
+                    if bool_key[4] {
+                        deep.zoom_by(1.0 / 0.999);
+                    }
+                    if bool_key[5] {
+                        deep.zoom_by(1.0 / 1.01);
+                    }
+
+                    deep.update(&mut pc);
+                    deep.sync_view(&mut fractal_view, pc.ires);
+                } else {
+                    let zoom = fractal_view.zoom;
+
+                    if bool_key[0] {
+                        fractal_view.cpos[1] += 0.001 * zoom;
+                    }
+                    if bool_key[1] {
+                        fractal_view.cpos[0] += 0.001 * zoom;
+                    }
+                    if bool_key[2] {
+                        fractal_view.cpos[1] -= 0.001 * zoom;
+                    }
+                    if bool_key[3] {
+                        fractal_view.cpos[0] -= 0.001 * zoom;
+                    }
+                    if bool_key[4] {
+                        fractal_view.zoom /= 0.999;
+                    }
+                    if bool_key[5] {
+                        fractal_view.zoom /= 1.01;
+                    }
+
+                    fractal_view.apply(&mut pc);
                 }
-                if bool_key[3] {
-                    FRAGMENT_PUSH_CONSTANTS.lock().unwrap().cpos[0] -= 0.001 * zoom;
+
+                // past what f64 can tell apart, so the Mandelbrot carries on in deep zoom, and back
+                // out of it on the way out. Only on crossing, so V still switches by hand.
+                let deep = DEEP_ZOOM.lock().unwrap().is_some();
+                let zoom = fractal_view.zoom;
+                if zoom < DEEP_ZOOM_BELOW
+                    && previous_zoom >= DEEP_ZOOM_BELOW
+                    && !deep
+                    && *FRACTAL_KIND.lock().unwrap() == FractalKind::Mandelbrot
+                {
+                    toggle_deep_zoom(&mut pc, &mut fractal_view);
                 }
-                if bool_key[4] {
-                    FRAGMENT_PUSH_CONSTANTS.lock().unwrap().zoom /= 0.999;
+                if zoom >= DEEP_ZOOM_BELOW && previous_zoom < DEEP_ZOOM_BELOW && deep {
+                    leave_deep_zoom(&mut pc, &mut fractal_view);
                 }
-                if bool_key[5] {
-                    FRAGMENT_PUSH_CONSTANTS.lock().unwrap().zoom /= 1.01;
+                previous_zoom = zoom;
+
+                if cycle_palette {
+                    pc.palette_offset = (pc.palette_offset + 0.002).fract();
                 }
+                drop((pc, fractal_view));
 
                 view_c.clone().lock().unwrap().if_recreate_swapchain(window_c.clone(), &mut vk_c.clone().lock().unwrap());
                 view_c.clone().lock().unwrap().update(&mut vk_c.clone().lock().unwrap());
-                *crate::vk_present::FRAGMENT_PUSH_CONSTANTS.lock().unwrap().time += 0.001;
 
-                presenter.present(&mut vk.clone().lock().unwrap(), &view.clone().lock().unwrap());
+                presenter.present(&mut vk.clone().lock().unwrap(), &mut view.clone().lock().unwrap());
                 // pr.clone().lock().unwrap().present(&mut vk.clone().lock().unwrap());
 
                 println!("MAIN: vk_present @ MainEventsCleared cleared within {:?}", then.elapsed());
+                if frame_id % 600 == 0 {
+                    println!("{}", MEMORY_TRACKER.lock().unwrap().stats(&vk.clone().lock().unwrap()));
+                }
                 frame_id += 1;
 
             },
@@ -146,3 +395,32 @@ pub fn run() {
         }
     });
 }
+
+/// 1 to 9 for the digit keys above the letters.
+fn digit(key: VirtualKeyCode) -> Option<u32> {
+    use VirtualKeyCode::*;
+    [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9]
+        .iter()
+        .position(|k| *k == key)
+        .map(|i| i as u32 + 1)
+}
+
+/// Switches between the Mandelbrot set and the Julia set of `c`. Entering Julia mode remembers
+/// the Mandelbrot view and centres the Julia set, leaving it restores that view. Either way the
+/// view jumps, so deep zoom is left first.
+fn toggle_julia(pc: &mut crate::vk_present::fs::PushConstantData, view: &mut FractalView, mandelbrot_view: &mut FractalView, c: [f32; 2]) {
+    leave_deep_zoom(pc, view);
+    if pc.julia == 0 {
+        *mandelbrot_view = *view;
+        pc.julia = 1;
+        pc.julia_c = c;
+        *view = FractalView {
+            cpos: [0.0, 0.0],
+            zoom: 1.5,
+        };
+        println!("julia c = {} {:+}i", c[0], c[1]);
+    } else {
+        pc.julia = 0;
+        *view = *mandelbrot_view;
+    }
+}
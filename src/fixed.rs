@@ -0,0 +1,333 @@
+use std::cmp::Ordering;
+
+/// Signed fixed point number of arbitrary precision. `limbs[0]` is the integer part and every
+/// following limb adds 64 bits of fraction, most significant first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fixed {
+    pub negative: bool,
+    pub limbs: Vec<u64>,
+}
+
+const LIMB: f64 = 18446744073709551616.0; // 2^64
+
+impl Fixed {
+    pub fn zero(limbs: usize) -> Self {
+        Self {
+            negative: false,
+            limbs: vec![0; limbs.max(1)],
+        }
+    }
+
+    /// Exact conversion, as long as `limbs` holds every bit of `value`.
+    pub fn from_f64(value: f64, limbs: usize) -> Self {
+        let mut result = Self::zero(limbs);
+        result.negative = value < 0.0;
+
+        let mut rest = value.abs();
+        for limb in result.limbs.iter_mut() {
+            let whole = rest.floor();
+            *limb = whole as u64;
+            rest = (rest - whole) * LIMB;
+        }
+
+        result
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let mut value = 0.0;
+        let mut scale = 1.0;
+        for limb in &self.limbs {
+            value += *limb as f64 * scale;
+            scale /= LIMB;
+        }
+
+        if self.negative { -value } else { value }
+    }
+
+    /// Same value with `limbs` limbs, truncating or zero extending the fraction.
+    pub fn with_limbs(&self, limbs: usize) -> Self {
+        let mut result = self.clone();
+        result.limbs.resize(limbs.max(1), 0);
+        result
+    }
+
+    fn cmp_magnitude(&self, other: &Self) -> Ordering {
+        self.limbs.cmp(&other.limbs)
+    }
+
+    fn add_magnitude(a: &[u64], b: &[u64]) -> Vec<u64> {
+        let mut result = vec![0; a.len()];
+        let mut carry = false;
+        for i in (0..a.len()).rev() {
+            let (sum, c1) = a[i].overflowing_add(b[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            result[i] = sum;
+            carry = c1 || c2;
+        }
+        result
+    }
+
+    // requires a >= b
+    fn sub_magnitude(a: &[u64], b: &[u64]) -> Vec<u64> {
+        let mut result = vec![0; a.len()];
+        let mut borrow = false;
+        for i in (0..a.len()).rev() {
+            let (diff, b1) = a[i].overflowing_sub(b[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            result[i] = diff;
+            borrow = b1 || b2;
+        }
+        result
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let other = other.with_limbs(self.limbs.len());
+
+        if self.negative == other.negative {
+            return Self {
+                negative: self.negative,
+                limbs: Self::add_magnitude(&self.limbs, &other.limbs),
+            };
+        }
+
+        match self.cmp_magnitude(&other) {
+            Ordering::Less => Self {
+                negative: other.negative,
+                limbs: Self::sub_magnitude(&other.limbs, &self.limbs),
+            },
+            _ => Self {
+                negative: self.negative,
+                limbs: Self::sub_magnitude(&self.limbs, &other.limbs),
+            },
+        }
+        .normalized()
+    }
+
+    pub fn neg(&self) -> Self {
+        Self {
+            negative: !self.negative,
+            limbs: self.limbs.clone(),
+        }
+        .normalized()
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    /// Product truncated to `self`'s precision.
+    pub fn mul(&self, other: &Self) -> Self {
+        let n = self.limbs.len();
+        let other = other.with_limbs(n);
+
+        // limb i of a times limb j of b lands on limb i + j; only the first n are kept, plus
+        // the carries out of the ones right after them
+        let mut wide = vec![0u128; 2 * n];
+        for i in 0..n {
+            let mut carry = 0u128;
+            for j in (0..n).rev() {
+                let k = i + j;
+                let product = self.limbs[i] as u128 * other.limbs[j] as u128 + wide[k + 1] + carry;
+                wide[k + 1] = product & u64::MAX as u128;
+                carry = product >> 64;
+            }
+            for limb in wide[..=i].iter_mut().rev() {
+                if carry == 0 {
+                    break;
+                }
+                let sum = *limb + carry;
+                *limb = sum & u64::MAX as u128;
+                carry = sum >> 64;
+            }
+        }
+
+        Self {
+            negative: self.negative != other.negative,
+            limbs: wide[1..=n].iter().map(|limb| *limb as u64).collect(),
+        }
+        .normalized()
+    }
+
+    pub fn mul_u64(&self, factor: u64) -> Self {
+        let mut limbs = vec![0; self.limbs.len()];
+        let mut carry = 0u128;
+        for i in (0..self.limbs.len()).rev() {
+            let product = self.limbs[i] as u128 * factor as u128 + carry;
+            limbs[i] = product as u64;
+            carry = product >> 64;
+        }
+
+        Self {
+            negative: self.negative,
+            limbs,
+        }
+    }
+
+    /// Quotient truncated to `self`'s precision.
+    pub fn div_u64(&self, divisor: u64) -> Self {
+        let mut limbs = vec![0; self.limbs.len()];
+        let mut remainder = 0u128;
+        for i in 0..self.limbs.len() {
+            let current = (remainder << 64) | self.limbs[i] as u128;
+            limbs[i] = (current / divisor as u128) as u64;
+            remainder = current % divisor as u128;
+        }
+
+        Self {
+            negative: self.negative,
+            limbs,
+        }
+        .normalized()
+    }
+
+    /// Decimal representation with up to `digits` fraction digits, rounded to nearest, without
+    /// trailing zeros. Rounding makes `parse_decimal`, which truncates, round trip.
+    pub fn to_decimal(&self, digits: usize) -> String {
+        // half of the last digit, added to the magnitude
+        let mut half = Self::zero(self.limbs.len());
+        half.limbs[0] = 5;
+        for _ in 0..=digits {
+            half = half.div_u64(10);
+        }
+        let magnitude = Self {
+            negative: false,
+            limbs: Self::add_magnitude(&self.limbs, &half.limbs),
+        };
+
+        let mut text = String::new();
+        if self.negative {
+            text.push('-');
+        }
+        text.push_str(&magnitude.limbs[0].to_string());
+        text.push('.');
+
+        let mut fraction = magnitude;
+        fraction.limbs[0] = 0;
+        for _ in 0..digits {
+            fraction = fraction.mul_u64(10);
+            text.push(char::from(b'0' + fraction.limbs[0] as u8));
+            fraction.limbs[0] = 0;
+        }
+
+        let trimmed = text.trim_end_matches('0').len();
+        text.truncate(trimmed);
+        if text.ends_with('.') {
+            text.push('0');
+        }
+        text
+    }
+
+    /// Parses `[-]digits[.digits]` into a number with `limbs` limbs.
+    pub fn parse_decimal(text: &str, limbs: usize) -> Option<Self> {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+        if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        // fraction digits from the last one: x = (d + x) / 10
+        let mut result = Self::zero(limbs);
+        for digit in fraction.bytes().rev() {
+            result.limbs[0] = (digit - b'0') as u64;
+            result = result.div_u64(10);
+        }
+        result.limbs[0] = integer.parse().ok()?;
+        result.negative = negative;
+
+        Some(result.normalized())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.limbs.iter().all(|limb| *limb == 0)
+    }
+
+    // zero is never negative, so that equal values compare equal
+    fn normalized(mut self) -> Self {
+        if self.is_zero() {
+            self.negative = false;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(negative: bool, limbs: &[u64]) -> Fixed {
+        Fixed {
+            negative,
+            limbs: limbs.to_vec(),
+        }
+    }
+
+    #[test]
+    fn add_carries_across_limbs() {
+        let a = fixed(false, &[1, u64::MAX, u64::MAX]);
+        let b = fixed(false, &[0, 0, 1]);
+        assert_eq!(a.add(&b), fixed(false, &[2, 0, 0]));
+    }
+
+    #[test]
+    fn sub_borrows_across_limbs() {
+        let a = fixed(false, &[2, 0, 0]);
+        let b = fixed(false, &[0, 0, 1]);
+        assert_eq!(a.sub(&b), fixed(false, &[1, u64::MAX, u64::MAX]));
+    }
+
+    #[test]
+    fn add_and_sub_with_negative_values() {
+        let a = Fixed::from_f64(1.25, 2);
+        let b = Fixed::from_f64(-3.5, 2);
+        assert_eq!(a.add(&b), Fixed::from_f64(-2.25, 2));
+        assert_eq!(b.sub(&a), Fixed::from_f64(-4.75, 2));
+        assert_eq!(b.sub(&b), Fixed::zero(2));
+        assert!(!a.sub(&a).negative);
+    }
+
+    #[test]
+    fn mul_carries_into_the_integer_limb() {
+        // (1 - 2^-64)^2 = 1 - 2^-63 + 2^-128
+        let a = fixed(false, &[0, u64::MAX, 0]);
+        assert_eq!(a.mul(&a), fixed(false, &[0, u64::MAX - 1, 1]));
+
+        let b = fixed(false, &[3, 1 << 63, 0]);
+        assert_eq!(b.mul(&b), Fixed::from_f64(12.25, 3));
+    }
+
+    #[test]
+    fn mul_signs() {
+        let a = Fixed::from_f64(-1.5, 2);
+        let b = Fixed::from_f64(2.5, 2);
+        assert_eq!(a.mul(&b), Fixed::from_f64(-3.75, 2));
+        assert_eq!(a.mul(&a), Fixed::from_f64(2.25, 2));
+        assert!(!a.mul(&Fixed::zero(2)).negative);
+    }
+
+    #[test]
+    fn with_limbs_widens_and_narrows() {
+        let a = fixed(true, &[7, 1, 2]);
+        assert_eq!(a.with_limbs(4), fixed(true, &[7, 1, 2, 0]));
+        assert_eq!(a.with_limbs(2), fixed(true, &[7, 1]));
+        assert_eq!(a.with_limbs(0), fixed(true, &[7]));
+        assert_eq!(a.with_limbs(4).with_limbs(3), a);
+    }
+
+    #[test]
+    fn decimal_round_trip() {
+        for text in ["0.0", "-1.5", "3.25", "-0.743643887037158704752191506114774", "0.1"] {
+            let parsed = Fixed::parse_decimal(text, 4).unwrap();
+            assert_eq!(parsed.to_decimal(40), text);
+        }
+    }
+
+    #[test]
+    fn parse_decimal_rejects_malformed_text() {
+        assert_eq!(Fixed::parse_decimal("1.2x", 2), None);
+        assert_eq!(Fixed::parse_decimal("x", 2), None);
+        assert_eq!(Fixed::parse_decimal("", 2), None);
+    }
+}
@@ -0,0 +1,202 @@
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use vulkano::shader::SpecializationConstant;
+
+use crate::vk_present::fs;
+
+/// Position and scale of the view. The shader gets each value as an f32 plus the f32 rounding
+/// error of that (`cpos_lo`, `zoom_lo`), enough for its double-double and f64 variants.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FractalView {
+    pub cpos: [f64; 2],
+    pub zoom: f64,
+}
+
+pub static FRACTAL_VIEW: Lazy<Mutex<FractalView>> = Lazy::new(|| {
+    Mutex::new(FractalView {
+        cpos: [0.0, 0.0],
+        zoom: 1.0,
+    })
+});
+
+fn split(value: f64) -> (f32, f32) {
+    let hi = value as f32;
+    (hi, (value - hi as f64) as f32)
+}
+
+impl FractalView {
+    pub fn apply(&self, pc: &mut fs::PushConstantData) {
+        let (x, x_lo) = split(self.cpos[0]);
+        let (y, y_lo) = split(self.cpos[1]);
+        let (zoom, zoom_lo) = split(self.zoom);
+
+        pc.cpos = [x, y];
+        pc.cpos_lo = [x_lo, y_lo];
+        pc.zoom = zoom;
+        pc.zoom_lo = zoom_lo;
+    }
+
+    /// Point of the complex plane under pixel `[x, y]` of an `ires` sized target, the same
+    /// mapping the fragment shader applies to its interpolated position.
+    pub fn pixel_to_complex(&self, ires: [f32; 2], [x, y]: [f32; 2]) -> [f64; 2] {
+        let [w, h] = [ires[0] as f64, ires[1] as f64];
+        let pos = [x as f64 / w * 2.0 - 1.0, y as f64 / h * 2.0 - 1.0];
+
+        [
+            pos[0] * self.zoom + 1.0 / w - self.cpos[0],
+            (pos[1] * self.zoom + 1.0 / h - self.cpos[1]) * h / w,
+        ]
+    }
+
+    /// Point of the complex plane at the centre of the view.
+    pub fn center(&self, ires: [f32; 2]) -> [f64; 2] {
+        self.pixel_to_complex(ires, [ires[0] * 0.5, ires[1] * 0.5])
+    }
+
+    /// Moves the view so that `center` ends up in the middle of it.
+    pub fn set_center(&mut self, ires: [f32; 2], [x, y]: [f64; 2]) {
+        let [w, h] = [ires[0] as f64, ires[1] as f64];
+        self.cpos = [1.0 / w - x, 1.0 / h - y * w / h];
+    }
+
+    /// View of the `size` pixels at `origin` of an `ires` sized target, drawn into a target of
+    /// exactly `size`, so that tiles rendered with it line up into the full image.
+    ///
+    /// Tiles are square: the shader measures zoom across the width, and a square tile keeps the
+    /// same scale against the full image along both axes.
+    pub fn tile(&self, ires: [f32; 2], origin: [u32; 2], size: [u32; 2]) -> FractalView {
+        debug_assert_eq!(size[0], size[1], "tiles are square");
+        let full = [ires[0] as f64, ires[1] as f64];
+        let scale = size[0] as f64 / full[0];
+        let cpos = [0, 1].map(|a| {
+            let t = size[a] as f64;
+            1.0 / t - scale / t * (self.zoom * (t + 2.0 * origin[a] as f64 - full[a]) + 1.0 - self.cpos[a] * full[a])
+        });
+
+        FractalView {
+            cpos,
+            zoom: self.zoom * scale,
+        }
+    }
+}
+
+/// Which variant of the fragment shader iterates the view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision {
+    F32,
+    /// Pairs of f32, for devices without `shader_float64`.
+    DoubleDouble,
+    F64,
+}
+
+impl Precision {
+    /// The cheapest precision that still tells pixels apart at `zoom`. Past what the wider
+    /// ones can do there is deep zoom.
+    pub fn for_zoom(zoom: f64, shader_float64: bool) -> Self {
+        if zoom > 1e-3 {
+            Precision::F32
+        } else if shader_float64 {
+            Precision::F64
+        } else {
+            Precision::DoubleDouble
+        }
+    }
+}
+
+/// Iterated formula, selected in the fragment shader through its `KIND` specialization constant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FractalKind {
+    Mandelbrot,
+    BurningShip,
+    /// Also known as the Mandelbar set, iterates the conjugate of z.
+    Tricorn,
+    /// z^power + c for any real `power`.
+    Multibrot,
+    Celtic,
+    /// Newton-Raphson on z^n - 1, n being `power` rounded.
+    Newton,
+}
+
+impl FractalKind {
+    pub const ALL: [FractalKind; 6] = [
+        FractalKind::Mandelbrot,
+        FractalKind::BurningShip,
+        FractalKind::Tricorn,
+        FractalKind::Multibrot,
+        FractalKind::Celtic,
+        FractalKind::Newton,
+    ];
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|kind| *kind == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// Specialization constants for the fragment shader, collected into whichever map
+    /// `ShaderModule::specialize` takes.
+    pub fn specialization<M: FromIterator<(u32, SpecializationConstant)>>(self) -> M {
+        [(0, SpecializationConstant::U32(self as u32))].into_iter().collect()
+    }
+}
+
+pub static FRACTAL_KIND: Lazy<Mutex<FractalKind>> = Lazy::new(|| {Mutex::new(FractalKind::Mandelbrot)} );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `pixel_point` of the shader at the centre of `pixel`.
+    fn point(view: &FractalView, ires: [f32; 2], pixel: [u32; 2]) -> [f64; 2] {
+        let [w, h] = [ires[0] as f64, ires[1] as f64];
+        let p = [0, 1].map(|a| (pixel[a] as f64 + 0.5) / ires[a] as f64 * 2.0 - 1.0);
+        let x = p[0] * view.zoom + 1.0 / w - view.cpos[0];
+        let y = p[1] * view.zoom + 1.0 / h - view.cpos[1];
+        [x, y * h / w]
+    }
+
+    #[test]
+    fn tiles_line_up_with_the_full_view() {
+        let view = FractalView { cpos: [0.6, -0.2], zoom: 1.5 };
+        let ires = [300.0, 200.0];
+        let tile = 128;
+
+        for origin in [[0, 0], [128, 0], [256, 128]] {
+            let tile_view = view.tile(ires, origin, [tile, tile]);
+            for pixel in [[0, 0], [5, 17], [127, 127]] {
+                let full = point(&view, ires, [origin[0] + pixel[0], origin[1] + pixel[1]]);
+                let tiled = point(&tile_view, [tile as f32; 2], pixel);
+                for a in 0..2 {
+                    assert!((full[a] - tiled[a]).abs() < 1e-12, "{origin:?} {pixel:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_of_a_different_aspect_keep_the_centre() {
+        let view = FractalView { cpos: [0.6, -0.2], zoom: 1.5 };
+        let window = [300.0, 200.0];
+        let center = view.center(window);
+
+        // taller than the window, the same span across
+        let ires = [256.0, 384.0];
+        let mut poster_view = view;
+        poster_view.set_center(ires, center);
+        let tile = 128;
+        let step = 2.0 * view.zoom / ires[0] as f64;
+
+        for origin in [[0, 0], [128, 128], [128, 256]] {
+            let tile_view = poster_view.tile(ires, origin, [tile, tile]);
+            for pixel in [[0, 0], [5, 17], [127, 127]] {
+                let tiled = point(&tile_view, [tile as f32; 2], pixel);
+                for a in 0..2 {
+                    let from_middle = (origin[a] + pixel[a]) as f64 + 0.5 - ires[a] as f64 / 2.0;
+                    let expected = center[a] + from_middle * step;
+                    assert!((expected - tiled[a]).abs() < 1e-12, "{origin:?} {pixel:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame};
+use once_cell::sync::Lazy;
+
+use crate::deep_zoom::DEEP_ZOOM;
+use crate::fractal::{FractalView, Precision, FRACTAL_VIEW};
+use crate::vk_offscreen::Offscreen;
+use crate::vk_present::{VkView, FRAGMENT_PUSH_CONSTANTS};
+use crate::vk_utils::Vk;
+
+/// What changes over a GIF loop. Both come back to where they started, so the loop has no seam.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GifLoop {
+    /// The palette cycles once through the view.
+    PaletteCycle,
+    /// Zooms in by `zoom_factor` and back out.
+    Zoom,
+}
+
+pub struct GifSettings {
+    pub size: [u32; 2],
+    pub frames: u32,
+    /// Time each frame is shown. GIF counts in hundredths of a second, so this is rounded to 10ms.
+    pub delay_ms: u32,
+    /// NeuQuant sampling of the colour quantization, 1 (best palettes, slowest) to 30 (fastest).
+    pub speed: i32,
+    pub mode: GifLoop,
+    pub zoom_factor: f64,
+}
+
+/// What `export_gif` renders, set from the command line.
+pub static GIF: Lazy<Mutex<GifSettings>> = Lazy::new(|| {
+    Mutex::new(GifSettings {
+        size: [480, 480],
+        frames: 60,
+        delay_ms: 40,
+        speed: 10,
+        mode: GifLoop::PaletteCycle,
+        zoom_factor: 0.1,
+    })
+});
+
+/// File name of a GIF loop of `view`, e.g. `loop_-0.743640_0.131820_1.5e-4.gif`.
+pub fn gif_path(view: &FractalView) -> PathBuf {
+    PathBuf::from(format!("loop_{:.6}_{:.6}_{:.1e}.gif", view.cpos[0], view.cpos[1], view.zoom))
+}
+
+/// Renders `GIF`'s loop of the current view offscreen and writes it as an animated GIF to the
+/// working directory.
+pub fn export_gif(vk: &Vk, view: &VkView) {
+    if DEEP_ZOOM.lock().unwrap().is_some() {
+        println!("gif export does not support deep zoom");
+        return;
+    }
+
+    let settings = GIF.lock().unwrap();
+    let base_pc = *FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+    let fractal_view = *FRACTAL_VIEW.lock().unwrap();
+    let center = fractal_view.center(base_pc.ires);
+
+    let deepest = match settings.mode {
+        GifLoop::PaletteCycle => fractal_view.zoom,
+        GifLoop::Zoom => fractal_view.zoom * settings.zoom_factor.min(1.0),
+    };
+    let precision = Precision::for_zoom(
+        deepest * base_pc.ires[0] as f64 / settings.size[0] as f64,
+        vk.device.enabled_features().shader_float64,
+    );
+    let offscreen = Offscreen::new(vk, view, settings.size, precision);
+
+    let mut frames = Vec::with_capacity(settings.frames as usize);
+    for frame in 0..settings.frames {
+        let t = frame as f64 / settings.frames as f64;
+        let mut pc = base_pc;
+        pc.ires = settings.size.map(|e| e as f32);
+
+        let mut frame_view = fractal_view;
+        match settings.mode {
+            GifLoop::PaletteCycle => pc.palette_offset = (base_pc.palette_offset + t as f32).fract(),
+            GifLoop::Zoom => {
+                // in for the first half, out for the second, at a steady rate in log space
+                let depth = 1.0 - (2.0 * t - 1.0).abs();
+                frame_view.zoom *= settings.zoom_factor.powf(depth);
+            }
+        }
+        // keep the centre of the window in the centre of the GIF
+        frame_view.set_center(pc.ires, center);
+        frame_view.apply(&mut pc);
+
+        frames.push(offscreen.render(vk, view, pc));
+    }
+
+    let path = gif_path(&fractal_view);
+    let (delay_ms, speed) = (settings.delay_ms, settings.speed);
+    // quantizing every frame takes a while
+    view.pool.execute(move || match save_gif(frames, &path, delay_ms, speed) {
+        Ok(()) => println!("saved gif to {}", path.display()),
+        Err(e) => println!("failed to save gif to {}: {e}", path.display()),
+    });
+}
+
+/// Writes `frames` as a GIF that loops forever.
+pub fn save_gif(frames: Vec<image::RgbaImage>, path: &Path, delay_ms: u32, speed: i32) -> image::ImageResult<()> {
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(path)?), speed);
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let delay = Delay::from_numer_denom_ms(delay_ms, 1);
+    encoder.encode_frames(frames.into_iter().map(|img| Frame::from_parts(img, 0, 0, delay)))
+}
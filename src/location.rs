@@ -0,0 +1,346 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::deep_zoom::{leave_deep_zoom, DeepZoom, DEEP_ZOOM, DEEP_ZOOM_BELOW};
+use crate::fixed::Fixed;
+use crate::fractal::{FractalKind, FractalView};
+use crate::distance::DistanceMode;
+use crate::orbit_trap::TrapMode;
+use crate::palette::PALETTES;
+use crate::vk_present::fs;
+
+/// Directory the numbered bookmark slots are kept in.
+const BOOKMARK_DIR: &str = "bookmarks";
+
+/// Everything needed to get back to a view, written as `key = value` lines:
+///
+/// ```text
+/// kind = Mandelbrot
+/// center_re = -0.743643887037158704752191506114774
+/// center_im = 0.131825904205311970493132056385139
+/// zoom = 1.5e-20
+/// max_iterations = 5000
+/// escape_radius = 4
+/// palette = classic
+/// palette_offset = 0.25
+/// palette_scale = 0.03125
+/// power = 3
+/// julia_c = -0.8 0.156
+/// trap = Circle 0.5 0 0
+/// distance = Relief 1 45 1.5
+/// ```
+///
+/// `julia_c` is only there for Julia sets, `trap` (mode, size and position) only with orbit
+/// trap colouring, `distance` (mode, outline width, light angle in degrees and light height)
+/// only with distance estimation. Unknown keys are ignored and other missing ones keep their current value,
+/// so older files still load.
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub kind: FractalKind,
+    /// Point of the complex plane at the centre of the view.
+    pub center: [Fixed; 2],
+    pub zoom: f64,
+    pub max_iterations: u32,
+    pub escape_radius: f32,
+    pub palette: usize,
+    pub palette_offset: f32,
+    pub palette_scale: f32,
+    pub power: f32,
+    pub julia_c: Option<[f32; 2]>,
+    pub trap: TrapMode,
+    pub trap_size: f32,
+    pub trap_pos: [f32; 2],
+    pub distance: DistanceMode,
+    pub distance_width: f32,
+    pub light_angle: f32,
+    pub light_height: f32,
+}
+
+impl Location {
+    /// The location on screen, centred on the deep zoom centre when deep zoom is on.
+    pub fn current(pc: &fs::PushConstantData, view: &FractalView, kind: FractalKind) -> Self {
+        let (center, zoom) = match DEEP_ZOOM.lock().unwrap().as_ref() {
+            Some(deep) => (deep.center.clone(), deep.zoom),
+            None => {
+                let [x, y] = view.center(pc.ires);
+                ([Fixed::from_f64(x, 2), Fixed::from_f64(y, 2)], view.zoom)
+            }
+        };
+
+        Self {
+            kind,
+            center,
+            zoom,
+            max_iterations: pc.max_iterations,
+            escape_radius: pc.escape_radius,
+            palette: pc.palette_index as usize,
+            palette_offset: pc.palette_offset,
+            palette_scale: pc.palette_scale,
+            power: pc.power,
+            julia_c: (pc.julia != 0).then_some(pc.julia_c),
+            trap: TrapMode::ALL[pc.trap_mode as usize],
+            trap_size: pc.trap_size,
+            trap_pos: pc.trap_pos,
+            distance: DistanceMode::ALL[pc.distance_mode as usize],
+            distance_width: pc.distance_width,
+            light_angle: pc.light_angle,
+            light_height: pc.light_height,
+        }
+    }
+
+    /// Moves the view to this location, switching deep zoom on or off as the zoom needs.
+    pub fn apply(&self, pc: &mut fs::PushConstantData, view: &mut FractalView, kind: &mut FractalKind) {
+        *kind = self.kind;
+        self.apply_params(pc);
+
+        if self.deep() {
+            let mut deep_zoom = DEEP_ZOOM.lock().unwrap();
+            let deep = deep_zoom.get_or_insert_with(|| DeepZoom::at(self.center.clone(), self.zoom));
+            deep.move_to(&self.center, self.zoom);
+            deep.update(pc);
+            deep.sync_view(view, pc.ires);
+        } else {
+            leave_deep_zoom(pc, view);
+            self.apply_view(pc, view);
+        }
+    }
+
+    /// Whether this location is shown in deep zoom.
+    pub fn deep(&self) -> bool {
+        self.zoom < DEEP_ZOOM_BELOW && self.kind == FractalKind::Mandelbrot
+    }
+
+    /// Points `view` at this location in f64, ignoring deep zoom.
+    pub fn apply_view(&self, pc: &mut fs::PushConstantData, view: &mut FractalView) {
+        view.zoom = self.zoom;
+        view.set_center(pc.ires, [self.center[0].to_f64(), self.center[1].to_f64()]);
+        view.apply(pc);
+    }
+
+    /// Everything but the kind and the view.
+    pub fn apply_params(&self, pc: &mut fs::PushConstantData) {
+        pc.max_iterations = self.max_iterations;
+        pc.escape_radius = self.escape_radius;
+        pc.palette_index = self.palette as u32;
+        pc.palette_offset = self.palette_offset;
+        pc.palette_scale = self.palette_scale;
+        pc.power = self.power;
+        pc.julia = self.julia_c.is_some() as u32;
+        if let Some(c) = self.julia_c {
+            pc.julia_c = c;
+        }
+        pc.trap_mode = self.trap as u32;
+        pc.trap_size = self.trap_size;
+        pc.trap_pos = self.trap_pos;
+        pc.distance_mode = self.distance as u32;
+        pc.distance_width = self.distance_width;
+        pc.light_angle = self.light_angle;
+        pc.light_height = self.light_height;
+    }
+
+    /// Reads `text` in the format written by `Display`, starting from `self`.
+    pub fn parse(&self, text: &str) -> Result<Self, String> {
+        let mut location = self.clone();
+        let mut center = [None, None];
+        let mut julia_c = None;
+        location.trap = TrapMode::Off;
+        location.distance = DistanceMode::Off;
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .filter(|(key, _)| !key.is_empty())
+                .ok_or_else(|| format!("line {}: expected `key = value`", n + 1))?;
+            let invalid = || format!("line {}: invalid {key} `{value}`", n + 1);
+
+            match key {
+                "kind" => {
+                    location.kind = *FractalKind::ALL
+                        .iter()
+                        .find(|kind| format!("{kind:?}") == value)
+                        .ok_or_else(invalid)?;
+                }
+                // parsed once the zoom is known
+                "center_re" => center[0] = Some((n, value)),
+                "center_im" => center[1] = Some((n, value)),
+                "zoom" => location.zoom = value.parse().map_err(|_| invalid())?,
+                "max_iterations" => location.max_iterations = value.parse().map_err(|_| invalid())?,
+                "escape_radius" => location.escape_radius = value.parse().map_err(|_| invalid())?,
+                "palette" => {
+                    location.palette = PALETTES
+                        .iter()
+                        .position(|palette| palette.name == value)
+                        .ok_or_else(invalid)?;
+                }
+                "palette_offset" => location.palette_offset = value.parse().map_err(|_| invalid())?,
+                "palette_scale" => location.palette_scale = value.parse().map_err(|_| invalid())?,
+                "power" => location.power = value.parse().map_err(|_| invalid())?,
+                "julia_c" => {
+                    let parts: Vec<f32> = value
+                        .split_whitespace()
+                        .map(|part| part.parse().map_err(|_| invalid()))
+                        .collect::<Result<_, _>>()?;
+                    julia_c = Some(<[f32; 2]>::try_from(parts).map_err(|_| invalid())?);
+                }
+                "trap" => {
+                    let mut parts = value.split_whitespace();
+                    let mode = parts.next().ok_or_else(invalid)?;
+                    location.trap = *TrapMode::ALL
+                        .iter()
+                        .find(|trap| format!("{trap:?}") == mode)
+                        .ok_or_else(invalid)?;
+
+                    let numbers: Vec<f32> = parts
+                        .map(|part| part.parse().map_err(|_| invalid()))
+                        .collect::<Result<_, _>>()?;
+                    let [size, x, y] = <[f32; 3]>::try_from(numbers).map_err(|_| invalid())?;
+                    location.trap_size = size;
+                    location.trap_pos = [x, y];
+                }
+                "distance" => {
+                    let mut parts = value.split_whitespace();
+                    let mode = parts.next().ok_or_else(invalid)?;
+                    location.distance = *DistanceMode::ALL
+                        .iter()
+                        .find(|distance| format!("{distance:?}") == mode)
+                        .ok_or_else(invalid)?;
+
+                    let numbers: Vec<f32> = parts
+                        .map(|part| part.parse().map_err(|_| invalid()))
+                        .collect::<Result<_, _>>()?;
+                    let [width, angle, height] = <[f32; 3]>::try_from(numbers).map_err(|_| invalid())?;
+                    location.distance_width = width;
+                    location.light_angle = angle.to_radians();
+                    location.light_height = height;
+                }
+                _ => {}
+            }
+        }
+
+        let limbs = DeepZoom::limbs(location.zoom);
+        for (i, line) in center.into_iter().enumerate() {
+            if let Some((n, value)) = line {
+                location.center[i] = Fixed::parse_decimal(value, limbs)
+                    .ok_or_else(|| format!("line {}: invalid centre `{value}`", n + 1))?;
+            }
+        }
+        location.julia_c = julia_c;
+        Ok(location)
+    }
+
+    /// Reads a location file, starting from `self` for anything it leaves out.
+    pub fn load(&self, path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        self.parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // every digit the centre holds, so that it reads back exactly; one short of its
+        // fraction bits, which parsing leaves the last of slightly off
+        let bits = 64 * (self.center[0].limbs.len() - 1);
+        let digits = (bits as f64 * std::f64::consts::LOG10_2) as usize - 1;
+
+        writeln!(f, "kind = {:?}", self.kind)?;
+        writeln!(f, "center_re = {}", self.center[0].to_decimal(digits))?;
+        writeln!(f, "center_im = {}", self.center[1].to_decimal(digits))?;
+        writeln!(f, "zoom = {:e}", self.zoom)?;
+        writeln!(f, "max_iterations = {}", self.max_iterations)?;
+        writeln!(f, "escape_radius = {}", self.escape_radius)?;
+        writeln!(f, "palette = {}", PALETTES[self.palette].name)?;
+        writeln!(f, "palette_offset = {}", self.palette_offset)?;
+        writeln!(f, "palette_scale = {}", self.palette_scale)?;
+        writeln!(f, "power = {}", self.power)?;
+        if let Some([x, y]) = self.julia_c {
+            writeln!(f, "julia_c = {x} {y}")?;
+        }
+        if self.trap != TrapMode::Off {
+            let [x, y] = self.trap_pos;
+            writeln!(f, "trap = {:?} {} {x} {y}", self.trap, self.trap_size)?;
+        }
+        if self.distance != DistanceMode::Off {
+            writeln!(
+                f,
+                "distance = {:?} {} {} {}",
+                self.distance,
+                self.distance_width,
+                self.light_angle.to_degrees(),
+                self.light_height,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// File of bookmark slot `slot`.
+pub fn slot_path(slot: u32) -> PathBuf {
+    Path::new(BOOKMARK_DIR).join(format!("{slot}.location"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fractal::FRACTAL_VIEW;
+    use crate::vk_present::FRAGMENT_PUSH_CONSTANTS;
+
+    const DEEP: &str = "\
+kind = Mandelbrot
+center_re = -0.743643887037158704752191506114774
+center_im = 0.131825904205311970493132056385139
+zoom = 1.5e-20
+max_iterations = 5000
+palette = classic
+julia_c = -0.8 0.156
+trap = Circle 0.5 0 0
+distance = Relief 1 45 1.5
+";
+
+    fn start() -> Location {
+        let pc = *FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+        Location::current(&pc, &FRACTAL_VIEW.lock().unwrap(), FractalKind::Mandelbrot)
+    }
+
+    #[test]
+    fn round_trips_through_text_at_full_precision() {
+        let location = start().parse(DEEP).unwrap();
+        let text = location.to_string();
+        assert!(text.contains("center_re = -0.743643887037158704752191506114774\n"), "{text}");
+        assert!(text.contains("center_im = 0.131825904205311970493132056385139\n"), "{text}");
+
+        let parsed = start().parse(&text).unwrap();
+        assert_eq!(parsed.center, location.center);
+        assert_eq!(parsed.zoom, location.zoom);
+        assert_eq!(parsed.julia_c, Some([-0.8, 0.156]));
+        assert_eq!(parsed.trap, TrapMode::Circle);
+        assert_eq!(parsed.distance, DistanceMode::Relief);
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        assert!(start().parse("zoom 1e-5").is_err());
+        assert!(start().parse("= 1e-5").is_err());
+        assert!(start().parse("zoom =").is_err());
+        assert!(start().parse("center_re = 0.1.2").is_err());
+        assert!(start().parse("trap = Circle 0.5").is_err());
+    }
+
+    #[test]
+    fn unknown_kind_is_an_error() {
+        assert!(start().parse("kind = Spiral").is_err());
+        assert!(start().parse("kind = mandelbrot").is_err());
+    }
+}
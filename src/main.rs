@@ -18,15 +18,170 @@ mod vk_utils;
 mod vk_present;
 mod buffer;
 mod vk_pipeline;
+mod vk_parallel;
+mod vk_upload;
+mod vk_readback;
+mod vk_screenshot;
 mod event_loop;
 mod camera;
+mod texture;
+mod vk_image;
+mod vk_memory;
+mod vk_registry;
+mod palette;
+mod fractal;
+mod fixed;
+mod deep_zoom;
+mod sampling;
+mod cpu_render;
+mod vk_offscreen;
+mod vk_iterations;
+mod poster;
+mod location;
+mod animation;
+mod y4m;
+mod gif_export;
+mod orbit_trap;
+mod distance;
+mod compare;
 
 use crate::vk_pipeline::Pipeline;
 
 
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Err(e) = start(&args) {
+        println!("{e}");
+    }
+}
+
+/// Handles the command line, then opens the interactive view unless a flag asked for
+/// something else.
+fn start(args: &[String]) -> Result<(), String> {
+    // `--location <file>` starts at a location saved by a bookmark
+    if let Some(path) = arg_value(args, "--location")? {
+        let mut pc = vk_present::FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+        let mut view = fractal::FRACTAL_VIEW.lock().unwrap();
+        let mut kind = fractal::FRACTAL_KIND.lock().unwrap();
+        match location::Location::current(&pc, &view, *kind).load(std::path::Path::new(path)) {
+            Ok(location) => location.apply(&mut pc, &mut view, &mut kind),
+            Err(e) => println!("failed to load location {e}"),
+        }
+    }
+
+    // `--animate <file>` renders an animation and exits, on the CPU with `--headless`
+    if let Some(path) = arg_value(args, "--animate")? {
+        let start = location::Location::current(
+            &vk_present::FRAGMENT_PUSH_CONSTANTS.lock().unwrap(),
+            &fractal::FRACTAL_VIEW.lock().unwrap(),
+            *fractal::FRACTAL_KIND.lock().unwrap(),
+        );
+        match animation::Animation::load(std::path::Path::new(path), &start) {
+            Ok(animation) if args.iter().any(|arg| arg == "--headless") => {
+                return animation.render_cpu().map_err(|e| format!("failed to render animation {e}"));
+            }
+            Ok(animation) => *animation::ANIMATION.lock().unwrap() = Some(animation),
+            Err(e) => return Err(format!("failed to load animation {e}")),
+        }
+    }
+
+    // `--cpu <path>` renders the starting view without touching Vulkan, for an sRGB target
+    // like most swapchains
+    if let Some(path) = arg_value(args, "--cpu")? {
+        if deep_zoom::DEEP_ZOOM.lock().unwrap().is_some() {
+            return Err("the CPU renderer does not support deep zoom".to_string());
+        }
+        let path = std::path::PathBuf::from(path);
+        let pc = *vk_present::FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+        let kind = *fractal::FRACTAL_KIND.lock().unwrap();
+        let img = cpu_render::render(&pc, kind, true, &threadpool::ThreadPool::new(12));
+        vk_screenshot::save_screenshot(img, &path);
+        return Ok(());
+    }
+
+    // `--compare <dir>` renders the starting view on the GPU and the CPU and checks they match
+    if let Some(dir) = arg_value(args, "--compare")? {
+        *compare::COMPARE.lock().unwrap() = Some(std::path::PathBuf::from(dir));
+    }
+
+    settings_from_args(args)?;
+
     // Initialization // 
     event_loop::run();
+    Ok(())
+}
+
+/// Screenshot, poster and GIF settings from their flags.
+fn settings_from_args(args: &[String]) -> Result<(), String> {
+    // `--screenshot-format jpg` makes F12 write JPEGs
+    if let Some(format) = arg_value(args, "--screenshot-format")? {
+        *vk_screenshot::SCREENSHOT_FORMAT.lock().unwrap() = match format {
+            "png" => "png",
+            "jpg" | "jpeg" => "jpg",
+            _ => return Err(invalid_arg("--screenshot-format", format, "png or jpg")),
+        };
+    }
+
+    if let Some(size) = arg_value(args, "--poster-size")? {
+        poster::POSTER.lock().unwrap().size = parse_size("--poster-size", size)?;
+    }
+    if let Some(samples) = arg_value(args, "--poster-samples")? {
+        poster::POSTER.lock().unwrap().samples = parse_arg("--poster-samples", samples)?;
+    }
+    if let Some(format) = arg_value(args, "--poster-format")? {
+        poster::POSTER.lock().unwrap().format = match format {
+            "png" => "png",
+            "tif" | "tiff" => "tiff",
+            _ => return Err(invalid_arg("--poster-format", format, "png or tiff")),
+        };
+    }
+
+    if let Some(size) = arg_value(args, "--gif-size")? {
+        gif_export::GIF.lock().unwrap().size = parse_size("--gif-size", size)?;
+    }
+    if let Some(frames) = arg_value(args, "--gif-frames")? {
+        gif_export::GIF.lock().unwrap().frames = parse_arg("--gif-frames", frames)?;
+    }
+    if let Some(delay) = arg_value(args, "--gif-delay")? {
+        gif_export::GIF.lock().unwrap().delay_ms = parse_arg("--gif-delay", delay)?;
+    }
+    if let Some(speed) = arg_value(args, "--gif-speed")? {
+        gif_export::GIF.lock().unwrap().speed = parse_arg::<i32>("--gif-speed", speed)?.clamp(1, 30);
+    }
+    if let Some(mode) = arg_value(args, "--gif-loop")? {
+        gif_export::GIF.lock().unwrap().mode = match mode {
+            "palette" => gif_export::GifLoop::PaletteCycle,
+            "zoom" => gif_export::GifLoop::Zoom,
+            _ => return Err(invalid_arg("--gif-loop", mode, "palette or zoom")),
+        };
+    }
+    if let Some(factor) = arg_value(args, "--gif-zoom")? {
+        gif_export::GIF.lock().unwrap().zoom_factor = parse_arg("--gif-zoom", factor)?;
+    }
+    Ok(())
+}
+
+fn invalid_arg(flag: &str, value: &str, expected: &str) -> String {
+    format!("invalid {flag} `{value}`, expected {expected}")
+}
+
+fn parse_arg<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| invalid_arg(flag, value, "a number"))
+}
+
+/// `WIDTHxHEIGHT`.
+fn parse_size(flag: &str, value: &str) -> Result<[u32; 2], String> {
+    let invalid = || invalid_arg(flag, value, "WIDTHxHEIGHT");
+    let (w, h) = value.split_once('x').ok_or_else(invalid)?;
+    Ok([w.parse().map_err(|_| invalid())?, h.parse().map_err(|_| invalid())?])
+}
+
+/// The argument following `flag`, if `flag` was given.
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>, String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(i) => args.get(i + 1).map(|value| Some(value.as_str())).ok_or_else(|| format!("{flag} needs a value")),
+        None => Ok(None),
+    }
 }
 
@@ -0,0 +1,37 @@
+/// Shape the orbit trap colouring measures the orbit's distance to, indexed by the `trap_mode`
+/// push constant. The palette is then looked up by the closest the orbit came to it instead of
+/// the escape time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrapMode {
+    /// Plain escape time colouring.
+    Off,
+    Point,
+    /// Horizontal line.
+    Line,
+    /// A horizontal and a vertical line.
+    Cross,
+    /// Circle with radius `trap_size`.
+    Circle,
+}
+
+impl TrapMode {
+    pub const ALL: [TrapMode; 5] = [
+        TrapMode::Off,
+        TrapMode::Point,
+        TrapMode::Line,
+        TrapMode::Cross,
+        TrapMode::Circle,
+    ];
+
+    /// Distance of `z` from the trap at `pos`, as the fragment shader measures it.
+    pub fn distance(self, [x, y]: [f64; 2], pos: [f64; 2], size: f64) -> f64 {
+        let [dx, dy] = [x - pos[0], y - pos[1]];
+        match self {
+            TrapMode::Off => f64::INFINITY,
+            TrapMode::Point => dx.hypot(dy),
+            TrapMode::Line => dy.abs(),
+            TrapMode::Cross => dx.abs().min(dy.abs()),
+            TrapMode::Circle => (dx.hypot(dy) - size).abs(),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use vulkano::format::{Format, NumericFormat};
+use vulkano::image::sampler::{Filter, SamplerAddressMode};
+
+use crate::texture::Texture;
+use crate::vk_image::ImageDesc;
+use crate::vk_upload::VkUploader;
+use crate::vk_utils::Vk;
+
+/// Texels per palette in the palette texture.
+pub const PALETTE_WIDTH: u32 = 256;
+
+/// A named colour gradient. Stops are sorted by position in [0, 1); the gradient wraps from
+/// the last stop back to the first so that cycling it has no seam.
+pub struct Palette {
+    pub name: &'static str,
+    pub stops: &'static [(f32, [u8; 3])],
+}
+
+pub const PALETTES: &[Palette] = &[
+    Palette {
+        name: "classic",
+        stops: &[
+            (0.0, [0, 7, 100]),
+            (0.16, [32, 107, 203]),
+            (0.42, [237, 255, 255]),
+            (0.6425, [255, 170, 0]),
+            (0.8575, [0, 2, 0]),
+        ],
+    },
+    Palette {
+        name: "fire",
+        stops: &[
+            (0.0, [0, 0, 0]),
+            (0.25, [128, 0, 0]),
+            (0.5, [255, 96, 0]),
+            (0.75, [255, 220, 64]),
+            (0.9, [255, 255, 255]),
+        ],
+    },
+    Palette {
+        name: "ocean",
+        stops: &[
+            (0.0, [0, 16, 32]),
+            (0.3, [0, 96, 128]),
+            (0.55, [64, 192, 200]),
+            (0.7, [224, 248, 255]),
+            (0.85, [0, 64, 96]),
+        ],
+    },
+    Palette {
+        name: "grayscale",
+        stops: &[
+            (0.0, [0, 0, 0]),
+            (0.5, [255, 255, 255]),
+        ],
+    },
+    Palette {
+        name: "rainbow",
+        stops: &[
+            (0.0, [255, 0, 0]),
+            (0.17, [255, 255, 0]),
+            (0.33, [0, 255, 0]),
+            (0.5, [0, 255, 255]),
+            (0.67, [0, 0, 255]),
+            (0.83, [255, 0, 255]),
+        ],
+    },
+];
+
+impl Palette {
+    /// Colour at `t`, wrapped into [0, 1) and linearly interpolated between the stops.
+    pub fn sample(&self, t: f32) -> [u8; 3] {
+        let t = t.rem_euclid(1.0);
+        let next = self.stops.iter().position(|(pos, _)| *pos > t).unwrap_or(0);
+        let prev = (next + self.stops.len() - 1) % self.stops.len();
+
+        let (p0, c0) = self.stops[prev];
+        let (mut p1, c1) = self.stops[next];
+        let mut t = t;
+        if p1 <= p0 {
+            // between the last stop and the first one, wrapping around 1.0
+            p1 += 1.0;
+            if t < p0 {
+                t += 1.0;
+            }
+        }
+
+        let f = (t - p0) / (p1 - p0);
+        std::array::from_fn(|i| (c0[i] as f32 + (c1[i] as f32 - c0[i] as f32) * f).round() as u8)
+    }
+
+    /// `PALETTE_WIDTH` RGBA8 texels, each sampled at its centre.
+    pub fn texels(&self) -> Vec<u8> {
+        (0..PALETTE_WIDTH)
+            .flat_map(|x| {
+                let [r, g, b] = self.sample((x as f32 + 0.5) / PALETTE_WIDTH as f32);
+                [r, g, b, 255]
+            })
+            .collect()
+    }
+}
+
+impl Texture {
+    /// Every palette in `PALETTES` as one layer of a 1D array texture, sampled with
+    /// `texture(palette, vec2(t, index))` and repeating in `t`.
+    ///
+    /// The stops are sRGB encoded colours. For an sRGB `target` format the texture is sRGB too,
+    /// so the colour written to the target is the colour of the stop either way.
+    pub fn palettes(vk: &Vk, uploader: &mut VkUploader, target: Format) -> Self {
+        let format = if target.numeric_format_color() == Some(NumericFormat::SRGB) {
+            Format::R8G8B8A8_SRGB
+        } else {
+            Format::R8G8B8A8_UNORM
+        };
+
+        let (image, view) = vk
+            .create_image(
+                &ImageDesc::new_1d(PALETTE_WIDTH)
+                    .format(format)
+                    .array_layers(PALETTES.len() as u32),
+            )
+            .unwrap();
+
+        let data: Vec<u8> = PALETTES.iter().flat_map(Palette::texels).collect();
+        uploader.image(vk, image.clone(), &data);
+
+        Self {
+            image,
+            view,
+            sampler: vk.sampler(Filter::Linear, SamplerAddressMode::Repeat, None),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use image::GenericImage;
+use once_cell::sync::Lazy;
+
+use crate::deep_zoom::{self, DEEP_ZOOM};
+use crate::fractal::{FractalView, Precision, FRACTAL_VIEW};
+use crate::vk_offscreen::Offscreen;
+use crate::vk_present::{VkView, FRAGMENT_PUSH_CONSTANTS};
+use crate::vk_screenshot::save_screenshot;
+use crate::vk_utils::Vk;
+
+/// Largest tile rendered in one go, below the device limits so a single draw stays well clear
+/// of driver timeouts.
+const MAX_TILE: u32 = 4096;
+
+pub struct PosterSettings {
+    pub size: [u32; 2],
+    /// Samples per pixel, overriding the view's own.
+    pub samples: u32,
+    /// Extension of the written file, "png" or "tiff".
+    pub format: &'static str,
+}
+
+/// What `export_poster` renders, set from the command line.
+pub static POSTER: Lazy<Mutex<PosterSettings>> = Lazy::new(|| {
+    Mutex::new(PosterSettings {
+        size: [8192, 8192],
+        samples: 4,
+        format: "png",
+    })
+});
+
+/// File name of a poster of `view`, e.g. `poster_-0.743640_0.131820_1.5e-4_8192x8192.png`.
+pub fn poster_path(view: &FractalView, settings: &PosterSettings) -> PathBuf {
+    PathBuf::from(format!(
+        "poster_{:.6}_{:.6}_{:.1e}_{}x{}.{}",
+        view.cpos[0],
+        view.cpos[1],
+        view.zoom,
+        settings.size[0],
+        settings.size[1],
+        settings.format,
+    ))
+}
+
+/// Renders the current view at `POSTER`'s size in tiles that fit the device, stitches them
+/// together and writes the result to the working directory. The picture is the window's,
+/// scaled to the poster's width. In deep zoom every tile iterates against the reference orbit
+/// of the window's centre.
+pub fn export_poster(vk: &Vk, view: &VkView) {
+    let deep = DEEP_ZOOM.lock().unwrap().is_some();
+    let settings = POSTER.lock().unwrap();
+    let [width, height] = settings.size;
+    let mut pc = *FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+    let window_ires = pc.ires;
+    let fractal_view = *FRACTAL_VIEW.lock().unwrap();
+
+    // the window shows pc.ires[0] pixels across, the poster `width` in the same span
+    let precision = Precision::for_zoom(
+        fractal_view.zoom * window_ires[0] as f64 / width as f64,
+        vk.device.enabled_features().shader_float64,
+    );
+
+    let tile = Offscreen::max_extent(vk, MAX_TILE);
+    let offscreen = Offscreen::new(vk, view, [tile, tile], precision);
+    let tiles = [width.div_ceil(tile), height.div_ceil(tile)];
+    let ires = [width as f32, height as f32];
+    pc.samples = settings.samples;
+
+    // cpos depends on the target's size, keep the window's centre in the poster's middle
+    let mut poster_view = fractal_view;
+    poster_view.set_center(ires, fractal_view.center(window_ires));
+
+    let mut poster = image::RgbaImage::new(width, height);
+    for ty in 0..tiles[1] {
+        for tx in 0..tiles[0] {
+            let origin = [tx * tile, ty * tile];
+            let img = if deep {
+                offscreen.render(vk, view, deep_zoom::tile(&pc, ires, origin, [tile, tile]))
+            } else {
+                poster_view.tile(ires, origin, [tile, tile]).apply(&mut pc);
+                offscreen.render(vk, view, pc)
+            };
+            let size = [tile.min(width - origin[0]), tile.min(height - origin[1])];
+            let part = image::imageops::crop_imm(&img, 0, 0, size[0], size[1]);
+            poster.copy_from(&*part, origin[0], origin[1]).unwrap();
+
+            println!("poster tile {}/{}", ty * tiles[0] + tx + 1, tiles[0] * tiles[1]);
+        }
+    }
+
+    let path = poster_path(&fractal_view, &settings);
+    // encoding a poster takes far longer than rendering it
+    view.pool.execute(move || save_screenshot(poster, &path));
+}
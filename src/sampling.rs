@@ -0,0 +1,24 @@
+/// Where the fragment shader places the samples of a pixel when supersampling, indexed by
+/// the `sample_pattern` push constant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplePattern {
+    /// Evenly spaced rows and columns.
+    Grid,
+    /// The grid turned by atan(1/2), so near horizontal and vertical edges get as many
+    /// distinct sample positions as there are samples.
+    RotatedGrid,
+    /// A low discrepancy sequence shifted by per pixel noise, which trades the regular
+    /// aliasing of a fixed pattern for fine grain.
+    BlueNoise,
+}
+
+impl SamplePattern {
+    pub const ALL: [SamplePattern; 3] = [
+        SamplePattern::Grid,
+        SamplePattern::RotatedGrid,
+        SamplePattern::BlueNoise,
+    ];
+}
+
+/// Samples per pixel to step through. Squares, so the grid patterns come out complete.
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 4, 9, 16];
@@ -0,0 +1,31 @@
+#version 460
+
+// Second pass of two-pass rendering: colours the features the fractal shader, specialized with
+// ITERATIONS, left in the iteration buffer. Runs every frame but iterates nothing, so palette
+// changes and cycling cost a texel fetch per pixel.
+
+layout(location = 0) out vec4 f_color;
+
+#include "common.glsl"
+
+// share of the escaping pixels of the iteration buffer below each bin edge, see
+// `equalization_cdf` in vk_iterations.rs
+layout(set = 0, binding = 2) readonly buffer Equalization {
+    highp float cdf[];
+};
+
+// `n` through the equalization, evened out so that every colour covers as many pixels
+highp float equalized(highp float n) {
+    highp float x = clamp(n, 0.0, 1.0) * float(cdf.length() - 1);
+    int k = min(int(x), cdf.length() - 2);
+    return mix(cdf[k], cdf[k + 1], x - float(k));
+}
+#define EQUALIZE
+
+#include "colour.glsl"
+
+layout(set = 0, binding = 1) uniform sampler2D iterations;
+
+void main() {
+    f_color = colour(texelFetch(iterations, ivec2(gl_FragCoord.xy), 0));
+}
@@ -0,0 +1,63 @@
+// Colouring shared by the fractal shader and the colouring pass of two-pass rendering. Both
+// colour from the same features of a pixel's orbit, so they draw the same picture:
+//
+//   x: smooth iteration count divided by max_iterations, 1.0 for points that never escape
+//   y: closest the orbit came to the trap
+//   z: distance from the boundary of the set in pixels
+//   w: angle of the relief normal
+
+layout(set = 0, binding = 0) uniform sampler1DArray palette;
+
+// escape time or orbit trap colouring
+vec4 base_colour(float n, float trap_distance) {
+    if (pc.trap_mode != TRAP_OFF) {
+        // palette by how close the orbit came, fading out with distance
+        highp float t = trap_distance / pc.trap_size;
+        highp vec3 rgb = texture(palette, vec2(t + pc.palette_offset, float(pc.palette_index))).rgb;
+        return vec4(rgb / (1.0 + t), 1.0);
+    }
+
+    if (n >= 1.0) {
+        return vec4(0.0, 0.0, 0.0, 1.0);
+    }
+
+    highp float t = n * float(pc.max_iterations) * pc.palette_scale + pc.palette_offset;
+#ifdef EQUALIZE
+    // once through the palette over the escaping pixels
+    if (pc.equalize != 0) {
+        t = equalized(n) + pc.palette_offset;
+    }
+#endif
+    return vec4(texture(palette, vec2(t, float(pc.palette_index))).rgb, 1.0);
+}
+
+// `rgba` shaded as a relief of the escape potential, lit from `light_angle`. The normal is
+// the direction of z / dz projected onto the plane.
+vec4 relief(vec4 rgba, float normal_angle) {
+    highp vec2 normal = vec2(cos(normal_angle), sin(normal_angle));
+    highp vec2 light = vec2(cos(pc.light_angle), sin(pc.light_angle));
+    highp float shade = (dot(normal, light) + pc.light_height) / (1.0 + pc.light_height);
+    return vec4(rgba.rgb * max(shade, 0.0), 1.0);
+}
+
+vec4 colour(vec4 features) {
+    highp float n = features.x;
+    highp vec4 rgba = base_colour(n, features.y);
+    if (!has_distance() || pc.distance_mode == DISTANCE_OFF) {
+        return rgba;
+    }
+
+    if (pc.distance_mode == DISTANCE_BOUNDARY) {
+        // white, darkening over `distance_width` pixels to the black of the set
+        highp float d = n >= 1.0 ? 0.0 : features.z / pc.distance_width;
+        return vec4(vec3(sqrt(clamp(d, 0.0, 1.0))), 1.0);
+    }
+    if (n >= 1.0) {
+        return rgba;
+    }
+    if (pc.distance_mode == DISTANCE_OUTLINE) {
+        // black within `distance_width` pixels of the boundary, a pixel of antialiasing
+        return vec4(rgba.rgb * clamp(features.z - pc.distance_width, 0.0, 1.0), 1.0);
+    }
+    return relief(rgba, features.w);
+}
@@ -0,0 +1,71 @@
+// Declarations shared by the fractal shader and the colouring pass of two-pass rendering.
+
+layout(push_constant) uniform PushConstantData {
+    highp vec2 cpos;
+    highp vec2 ires;
+    highp float zoom;
+    uint max_iterations;
+    highp float escape_radius;
+    uint palette_index;
+    // palette position at iteration 0, and palette lengths per iteration
+    highp float palette_offset;
+    highp float palette_scale;
+    // iterate z from the pixel with this fixed c instead of from 0 with the pixel as c
+    highp vec2 julia_c;
+    uint julia;
+    // exponent of Multibrot, degree of the Newton polynomial z^n - 1
+    highp float power;
+    // deep zoom: length of the reference orbit, 0 when off, and the zoom is
+    // zoom * 2^scale_exp
+    uint reference_len;
+    int scale_exp;
+    // rounding error of cpos and zoom, for the wider precision variants
+    highp vec2 cpos_lo;
+    highp float zoom_lo;
+    // supersampling: samples per pixel, SamplePattern in sampling.rs, and whether only
+    // pixels that differ from their neighbours get more than one
+    uint samples;
+    uint sample_pattern;
+    uint adaptive;
+    // orbit trap: TrapMode in orbit_trap.rs, 0 for escape time colouring, the distance
+    // that counts as close, and the point the point, circle and cross traps are centred on
+    // and the line trap passes through
+    uint trap_mode;
+    highp float trap_size;
+    highp vec2 trap_pos;
+    // distance estimation: DistanceMode in distance.rs, the outline width in pixels, and the
+    // direction of the light over the relief and its height
+    uint distance_mode;
+    highp float distance_width;
+    highp float light_angle;
+    highp float light_height;
+    // histogram equalization, only done by the colouring pass of two-pass rendering
+    uint equalize;
+} pc;
+
+// FractalKind, see fractal.rs
+layout(constant_id = 0) const uint KIND = 0;
+const uint MANDELBROT = 0;
+const uint BURNING_SHIP = 1;
+const uint TRICORN = 2;
+const uint MULTIBROT = 3;
+const uint CELTIC = 4;
+const uint NEWTON = 5;
+
+// TrapMode, see orbit_trap.rs
+const uint TRAP_OFF = 0;
+const uint TRAP_POINT = 1;
+const uint TRAP_LINE = 2;
+const uint TRAP_CROSS = 3;
+const uint TRAP_CIRCLE = 4;
+
+// DistanceMode, see distance.rs
+const uint DISTANCE_OFF = 0;
+const uint DISTANCE_BOUNDARY = 1;
+const uint DISTANCE_OUTLINE = 2;
+const uint DISTANCE_RELIEF = 3;
+
+// only these are holomorphic, the others have no derivative to estimate from
+bool has_distance() {
+    return KIND == MANDELBROT || KIND == MULTIBROT;
+}
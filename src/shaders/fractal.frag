@@ -0,0 +1,411 @@
+#version 460
+
+// Compiled three times: plain f32, with DOUBLE_DOUBLE and with FLOAT64, which only differ in the
+// precision of the escape time iteration (see Precision in fractal.rs). Specialized with
+// ITERATIONS it is the first pass of two-pass rendering and writes the features colour.glsl
+// colours from instead of colours, see vk_iterations.rs.
+
+layout(location = 0) out vec4 f_color;
+layout(location = 0) in vec3 pos;
+
+#include "common.glsl"
+#include "colour.glsl"
+
+layout(constant_id = 1) const bool ITERATIONS = false;
+
+// orbit of the view centre, computed at high precision on the CPU
+layout(set = 0, binding = 1) readonly buffer ReferenceOrbit {
+    vec2 orbit[];
+} reference;
+
+// closest the orbit of the pixel being iterated came to the trap
+float trap_min;
+
+void trap(vec2 z) {
+    if (pc.trap_mode == TRAP_OFF) {
+        return;
+    }
+
+    highp vec2 d = z - pc.trap_pos;
+    highp float distance;
+    if (pc.trap_mode == TRAP_POINT) {
+        distance = length(d);
+    } else if (pc.trap_mode == TRAP_LINE) {
+        distance = abs(d.y);
+    } else if (pc.trap_mode == TRAP_CROSS) {
+        distance = min(abs(d.x), abs(d.y));
+    } else {
+        distance = abs(length(d) - pc.trap_size);
+    }
+    trap_min = min(trap_min, distance);
+}
+
+// derivative of the orbit by c, or by the starting point for Julia sets, as a mantissa and a
+// power of 2, since near the boundary it grows past the f32 range. And the z it escaped with.
+highp vec2 derivative;
+int derivative_exp;
+highp vec2 escaped_z;
+
+vec2 cmul(vec2 a, vec2 b) {
+    return vec2(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+vec2 cdiv(vec2 a, vec2 b) {
+    return vec2(a.x * b.x + a.y * b.y, a.y * b.x - a.x * b.y) / dot(b, b);
+}
+
+vec2 cpow(vec2 z, float p) {
+    highp float r = pow(dot(z, z), p * 0.5);
+    highp float a = atan(z.y, z.x) * p;
+    return r * vec2(cos(a), sin(a));
+}
+
+void start_derivative() {
+    derivative = pc.julia != 0 ? vec2(1.0, 0.0) : vec2(0.0);
+    derivative_exp = 0;
+}
+
+// steps the derivative along with z, before z itself steps: dz' = 2 z dz + 1
+void track_derivative(vec2 z) {
+    if (!has_distance() || pc.distance_mode == DISTANCE_OFF) {
+        return;
+    }
+
+    if (KIND == MULTIBROT) {
+        derivative = pc.power * cmul(cpow(z, pc.power - 1.0), derivative);
+    } else {
+        derivative = 2.0 * cmul(z, derivative);
+    }
+    if (pc.julia == 0) {
+        derivative += vec2(ldexp(1.0, -derivative_exp), 0.0);
+    }
+
+    if (max(abs(derivative.x), abs(derivative.y)) > 1.8446744e19) {
+        derivative = ldexp(derivative, ivec2(-64));
+        derivative_exp += 64;
+    }
+}
+
+// Squared bailout radius. The distance estimate is only accurate for z well past the escape
+// radius, the smooth iteration count does not depend on it.
+float bailout() {
+    highp float r2 = pc.escape_radius * pc.escape_radius;
+    return has_distance() && pc.distance_mode != DISTANCE_OFF ? max(r2, 1e6) : r2;
+}
+
+// `real` is the scalar the escape time iteration runs in
+#if defined(FLOAT64)
+    #define real double
+
+    real r_make(float hi, float lo) { return double(hi) + double(lo); }
+    float r_float(real a) { return float(a); }
+    real r_add(real a, real b) { return a + b; }
+    real r_mul(real a, real b) { return a * b; }
+    real r_abs(real a) { return abs(a); }
+#elif defined(DOUBLE_DOUBLE)
+    // unevaluated sum hi + lo of two floats, about 48 bits of mantissa. `precise` keeps the
+    // compiler from simplifying away the rounding errors these rely on.
+    #define real vec2
+
+    real quick_two_sum(float a, float b) {
+        precise float s = a + b;
+        precise float e = b - (s - a);
+        return vec2(s, e);
+    }
+
+    real two_sum(float a, float b) {
+        precise float s = a + b;
+        precise float v = s - a;
+        precise float e = (a - (s - v)) + (b - v);
+        return vec2(s, e);
+    }
+
+    real r_make(float hi, float lo) { return quick_two_sum(hi, lo); }
+    float r_float(real a) { return a.x + a.y; }
+
+    real r_add(real a, real b) {
+        real s = two_sum(a.x, b.x);
+        precise float e = s.y + a.y + b.y;
+        return quick_two_sum(s.x, e);
+    }
+
+    real r_mul(real a, real b) {
+        precise float p = a.x * b.x;
+        precise float e = fma(a.x, b.x, -p) + (a.x * b.y + a.y * b.x);
+        return quick_two_sum(p, e);
+    }
+
+    real r_abs(real a) { return a.x < 0.0 ? -a : a; }
+#else
+    #define real float
+
+    real r_make(float hi, float lo) { return hi + lo; }
+    float r_float(real a) { return a; }
+    real r_add(real a, real b) { return a + b; }
+    real r_mul(real a, real b) { return a * b; }
+    real r_abs(real a) { return abs(a); }
+#endif
+
+real r_sub(real a, real b) { return r_add(a, -b); }
+
+struct complex {
+    real re;
+    real im;
+};
+
+complex c_make(vec2 z) {
+    return complex(r_make(z.x, 0.0), r_make(z.y, 0.0));
+}
+
+vec2 c_vec2(complex z) {
+    return vec2(r_float(z.re), r_float(z.im));
+}
+
+complex step_z(complex z, complex c) {
+    if (KIND == BURNING_SHIP) {
+        z = complex(r_abs(z.re), r_abs(z.im));
+    } else if (KIND == TRICORN) {
+        z.im = -z.im;
+    } else if (KIND == MULTIBROT) {
+        // no pow or atan beyond f32
+        return c_make(cpow(c_vec2(z), pc.power) + c_vec2(c));
+    }
+
+    real xy = r_mul(z.re, z.im);
+    complex z2 = complex(r_sub(r_mul(z.re, z.re), r_mul(z.im, z.im)), r_add(xy, xy));
+    if (KIND == CELTIC) {
+        z2.re = r_abs(z2.re);
+    }
+    return complex(r_add(z2.re, c.re), r_add(z2.im, c.im));
+}
+
+// smooth iteration count divided by max_iterations, 1.0 for points that never escape
+float escape_time(complex w, complex c) {
+    highp float r2 = bailout();
+    highp vec2 z = c_vec2(w);
+    uint i;
+    trap_min = 1e20;
+    start_derivative();
+
+    for (i = 0; i < pc.max_iterations; ++i) {
+        track_derivative(z);
+        w = step_z(w, c);
+        z = c_vec2(w);
+        trap(z);
+
+        if (dot(z, z) > r2) {
+            break;
+        }
+    }
+
+    if (i == pc.max_iterations) {
+        return 1.0;
+    }
+    escaped_z = z;
+
+    // fraction of an iteration by how far past the escape radius z landed, which
+    // removes the banding between whole iteration counts
+    highp float degree = KIND == MULTIBROT ? pc.power : 2.0;
+    highp float nu = float(i) + 1.0 - log(log(dot(z, z)) / log(r2)) / log(degree);
+    return nu / float(pc.max_iterations);
+}
+
+// Newton-Raphson on z^n - 1 started from the pixel, smooth iteration count until it
+// settles on a root, 1.0 if it never does
+float newton(highp vec2 z) {
+    highp float n = max(round(pc.power), 2.0);
+    highp float tolerance = 1e-6;
+    highp float d2 = 1.0;
+    uint i;
+    trap_min = 1e20;
+
+    for (i = 0; i < pc.max_iterations; ++i) {
+        highp vec2 zn1 = cpow(z, n - 1.0);
+        highp vec2 step = cdiv(cmul(zn1, z) - vec2(1.0, 0.0), n * zn1);
+        z -= step;
+        trap(z);
+
+        d2 = dot(step, step);
+        if (d2 < tolerance) {
+            break;
+        }
+    }
+
+    if (i == pc.max_iterations) {
+        return 1.0;
+    }
+
+    // convergence is quadratic, so log(d2) about doubles every iteration
+    highp float nu = float(i) + 1.0 - log2(log(d2) / log(tolerance));
+    return clamp(nu, 0.0, float(pc.max_iterations) - 1.0) / float(pc.max_iterations);
+}
+
+// Perturbation: each pixel iterates its offset dz from the reference orbit Z,
+// z = Z + dz, as dz' = 2 Z dz + dz^2 + dc. Offsets are stored as mantissa * 2^e so
+// they do not underflow at zooms far past the f32 range.
+float perturbed(vec2 offset) {
+    int e = pc.scale_exp;
+    highp vec2 dz = pc.julia != 0 ? offset : vec2(0.0);
+    highp vec2 dc = pc.julia != 0 ? vec2(0.0) : offset;
+    highp float r2 = bailout();
+    highp vec2 z = reference.orbit[0] + ldexp(dz, ivec2(e));
+    uint n = 0;
+    uint i;
+    trap_min = 1e20;
+    start_derivative();
+
+    for (i = 0; i < pc.max_iterations; ++i) {
+        track_derivative(z);
+        highp vec2 Z = reference.orbit[n];
+        dz = 2.0 * cmul(Z, dz) + ldexp(cmul(dz, dz), ivec2(e)) + ldexp(dc, ivec2(pc.scale_exp - e));
+        ++n;
+
+        // keep the mantissa at most 1 until dz is large enough to be stored as is
+        if (e < 0) {
+            // frexp exponent of the larger component
+            int k = ((floatBitsToInt(max(abs(dz.x), abs(dz.y))) >> 23) & 0xff) - 126;
+            k = clamp(k, 0, -e);
+            dz = ldexp(dz, ivec2(-k));
+            e += k;
+        }
+
+        highp vec2 dz_abs = ldexp(dz, ivec2(e));
+        z = reference.orbit[n] + dz_abs;
+        trap(z);
+        if (dot(z, z) > r2) {
+            break;
+        }
+
+        // glitch: z is nearer 0 than to the reference, whose orbit then no longer
+        // describes this pixel, or the reference escaped first. Rebase onto the
+        // start of the reference orbit and carry on from there.
+        if (dot(z, z) < dot(dz_abs, dz_abs) || n == pc.reference_len - 1) {
+            dz = ldexp(z - reference.orbit[0], ivec2(-e));
+            n = 0;
+        }
+    }
+
+    if (i == pc.max_iterations) {
+        return 1.0;
+    }
+    escaped_z = z;
+
+    highp float nu = float(i) + 1.0 - log2(log(dot(z, z)) / log(r2));
+    return nu / float(pc.max_iterations);
+}
+
+// Point of the complex plane at position `p` of the view, in full precision.
+complex pixel_point(vec2 p) {
+    real zoom = r_make(pc.zoom, pc.zoom_lo);
+    real x = r_add(r_mul(r_make(p.x, 0.0), zoom), r_make(1.0 / pc.ires.x, 0.0));
+    real y = r_add(r_mul(r_make(p.y, 0.0), zoom), r_make(1.0 / pc.ires.y, 0.0));
+    x = r_sub(x, r_make(pc.cpos.x, pc.cpos_lo.x));
+    y = r_sub(y, r_make(pc.cpos.y, pc.cpos_lo.y));
+    return complex(x, r_mul(y, r_make(pc.ires.y / pc.ires.x, 0.0)));
+}
+
+float fractal(complex p) {
+    if (KIND == NEWTON) {
+        return newton(c_vec2(p));
+    }
+    return pc.julia != 0 ? escape_time(p, c_make(pc.julia_c)) : escape_time(c_make(vec2(0.0)), p);
+}
+
+// Distance of the pixel from the boundary of the set in pixels, |z| ln|z| / |dz|
+float boundary_distance() {
+    highp float r = length(escaped_z);
+    highp float pixel = 2.0 * pc.zoom / pc.ires.x;
+    int e = derivative_exp + (pc.reference_len > 0 ? pc.scale_exp : 0);
+    return ldexp(r * log(r) / length(derivative) / pixel, -e);
+}
+
+// what colouring needs from the orbit just iterated, which escaped after `n`, see colour.glsl
+vec4 features(float n) {
+    if (!has_distance() || pc.distance_mode == DISTANCE_OFF || n >= 1.0) {
+        return vec4(n, trap_min, 0.0, 0.0);
+    }
+    highp vec2 normal = cdiv(escaped_z, derivative);
+    return vec4(n, trap_min, boundary_distance(), atan(normal.y, normal.x));
+}
+
+// features of the orbit at position `p` of the view
+vec4 features_at(vec2 p) {
+    if (KIND == MANDELBROT && pc.reference_len > 0) {
+        // cpos is where the view is centred relative to the reference, non-zero for tiles
+        return features(perturbed(vec2(p.x, p.y * pc.ires.y / pc.ires.x) * pc.zoom + pc.cpos));
+    }
+    return features(fractal(pixel_point(p)));
+}
+
+// colour at position `p` of the view
+vec4 colour_at(vec2 p) {
+    return colour(features_at(p));
+}
+
+// SamplePattern, see sampling.rs
+const uint GRID = 0;
+const uint ROTATED_GRID = 1;
+const uint BLUE_NOISE = 2;
+
+// Interleaved gradient noise, a per pixel value in [0, 1) with most of its energy at high
+// frequencies, so the error it leaves reads as fine grain rather than blotches.
+float pixel_noise(vec2 frag_coord) {
+    return fract(52.9829189 * fract(dot(frag_coord, vec2(0.06711056, 0.00583715))));
+}
+
+// offset in pixels from the pixel centre of sample `s` out of `n`
+vec2 sample_offset(uint s, uint n) {
+    if (pc.sample_pattern == BLUE_NOISE) {
+        // R2 low discrepancy sequence, shifted by a different amount in every pixel
+        highp vec2 r2 = vec2(0.7548776662, 0.5698402910) * float(s + 1);
+        return fract(r2 + pixel_noise(gl_FragCoord.xy)) - 0.5;
+    }
+
+    uint side = uint(ceil(sqrt(float(n))));
+    highp vec2 cell = (vec2(s % side, s / side) + 0.5) / float(side) - 0.5;
+    if (pc.sample_pattern == ROTATED_GRID) {
+        // rotated by atan(1/2) so no two samples share a row or column, wrapped back
+        // into the pixel
+        const vec2 r = vec2(0.8944272, 0.4472136);
+        cell = fract(vec2(cell.x * r.x - cell.y * r.y, cell.x * r.y + cell.y * r.x) + 0.5) - 0.5;
+    }
+    return cell;
+}
+
+vec4 supersampled(uint n) {
+    highp vec4 sum = vec4(0.0);
+    for (uint s = 0; s < n; ++s) {
+        // 2 / ires is a pixel in `pos` units
+        sum += colour_at(pos.xy + sample_offset(s, n) * 2.0 / pc.ires);
+    }
+    return sum / float(n);
+}
+
+void main() {
+    // one sample per pixel, the colouring pass reads them back by pixel
+    if (ITERATIONS) {
+        f_color = features_at(pos.xy);
+        return;
+    }
+
+    if (pc.samples <= 1) {
+        f_color = colour_at(pos.xy);
+        return;
+    }
+
+    if (pc.adaptive == 0) {
+        f_color = supersampled(pc.samples);
+        return;
+    }
+
+    // Adaptive: one sample everywhere, all of them only where it differs from the
+    // neighbouring pixels. fwidth compares within the 2x2 quad, so it has to be taken
+    // before any pixel branches off.
+    highp vec4 centre = colour_at(pos.xy);
+    highp vec3 edge = fwidth(centre.rgb);
+    if (max(edge.r, max(edge.g, edge.b)) > 0.05) {
+        f_color = supersampled(pc.samples);
+    } else {
+        f_color = centre;
+    }
+}
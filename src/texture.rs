@@ -0,0 +1,166 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use vulkano::command_buffer::{BlitImageInfo, ImageBlit};
+use vulkano::format::{Format, FormatFeatures};
+use vulkano::image::sampler::{
+    Filter, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode, LOD_CLAMP_NONE,
+};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageSubresourceLayers, ImageUsage};
+
+use crate::vk_image::ImageDesc;
+use crate::vk_upload::VkUploader;
+use crate::vk_utils::Vk;
+
+/// A sampled image with its full mip chain, a default view and a sampler.
+pub struct Texture {
+    pub image: Arc<Image>,
+    pub view: Arc<ImageView>,
+    pub sampler: Arc<Sampler>,
+}
+
+impl Texture {
+    /// Loads a PNG/JPEG/HDR/... file. 8 bit images are treated as colour data and stored as
+    /// sRGB when `srgb` is set (unset for palettes of raw values, normal maps, etc.), HDR images
+    /// are stored as 32 bit floats.
+    ///
+    /// The copies are recorded into `uploader`; flush it and wait before sampling the texture.
+    pub fn from_file(
+        vk: &Vk,
+        uploader: &mut VkUploader,
+        path: impl AsRef<Path>,
+        srgb: bool,
+        sampler: Arc<Sampler>,
+    ) -> image::ImageResult<Self> {
+        let img = image::open(path)?;
+
+        Ok(match img {
+            image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_) => {
+                let img = img.to_rgba32f();
+                Self::from_pixels(
+                    vk,
+                    uploader,
+                    Format::R32G32B32A32_SFLOAT,
+                    [img.width(), img.height()],
+                    bytemuck::cast_slice(img.as_raw()),
+                    sampler,
+                )
+            }
+            _ => {
+                let img = img.to_rgba8();
+                let format = if srgb { Format::R8G8B8A8_SRGB } else { Format::R8G8B8A8_UNORM };
+                Self::from_pixels(
+                    vk,
+                    uploader,
+                    format,
+                    [img.width(), img.height()],
+                    img.as_raw(),
+                    sampler,
+                )
+            }
+        })
+    }
+
+    /// Creates a texture from tightly packed texels of `format`. A mip chain is generated with
+    /// linear blits when the format supports it, otherwise the texture has a single level.
+    pub fn from_pixels(
+        vk: &Vk,
+        uploader: &mut VkUploader,
+        format: Format,
+        [width, height]: [u32; 2],
+        data: &[u8],
+        sampler: Arc<Sampler>,
+    ) -> Self {
+        let blit_features = FormatFeatures::SAMPLED_IMAGE_FILTER_LINEAR
+            | FormatFeatures::BLIT_SRC
+            | FormatFeatures::BLIT_DST;
+        let can_blit = vk.physical_device
+            .format_properties(format)
+            .unwrap()
+            .optimal_tiling_features
+            .contains(blit_features);
+
+        let mut desc = ImageDesc::new_2d([width, height])
+            .format(format)
+            .usage(ImageUsage::TRANSFER_DST | ImageUsage::TRANSFER_SRC | ImageUsage::SAMPLED);
+        if can_blit {
+            desc = desc.full_mip_chain();
+        }
+        let mip_levels = desc.mip_levels;
+
+        let (image, view) = vk.create_image(&desc).unwrap();
+
+        uploader.image(vk, image.clone(), data);
+
+        // each level is blitted from the previous one, in the same batch as the upload
+        for level in 1..mip_levels {
+            let src_extent = mip_extent([width, height], level - 1);
+            let dst_extent = mip_extent([width, height], level);
+
+            uploader
+                .builder(vk)
+                .blit_image(BlitImageInfo {
+                    regions: [ImageBlit {
+                        src_subresource: ImageSubresourceLayers {
+                            mip_level: level - 1,
+                            ..image.subresource_layers()
+                        },
+                        src_offsets: [[0, 0, 0], [src_extent[0], src_extent[1], 1]],
+                        dst_subresource: ImageSubresourceLayers {
+                            mip_level: level,
+                            ..image.subresource_layers()
+                        },
+                        dst_offsets: [[0, 0, 0], [dst_extent[0], dst_extent[1], 1]],
+                        ..Default::default()
+                    }]
+                    .into(),
+                    filter: Filter::Linear,
+                    ..BlitImageInfo::images(image.clone(), image.clone())
+                })
+                .unwrap();
+        }
+
+        Self {
+            image,
+            view,
+            sampler,
+        }
+    }
+}
+
+fn mip_extent([width, height]: [u32; 2], level: u32) -> [u32; 2] {
+    [(width >> level).max(1), (height >> level).max(1)]
+}
+
+impl Vk {
+    /// Creates a sampler covering every mip level. `anisotropy` is clamped to the device limit
+    /// and ignored if the device does not support anisotropic filtering.
+    pub fn sampler(
+        &self,
+        filter: Filter,
+        address_mode: SamplerAddressMode,
+        anisotropy: Option<f32>,
+    ) -> Arc<Sampler> {
+        let anisotropy = anisotropy
+            .filter(|_| self.device.enabled_features().sampler_anisotropy)
+            .map(|a| a.clamp(1.0, self.physical_device.properties().max_sampler_anisotropy));
+
+        Sampler::new(
+            self.device.clone(),
+            SamplerCreateInfo {
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_mode: match filter {
+                    Filter::Nearest => SamplerMipmapMode::Nearest,
+                    _ => SamplerMipmapMode::Linear,
+                },
+                address_mode: [address_mode; 3],
+                anisotropy,
+                lod: 0.0..=LOD_CLAMP_NONE,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+}
@@ -0,0 +1,309 @@
+use std::fmt;
+use std::sync::Arc;
+
+use vulkano::format::{Format, FormatFeatures};
+use vulkano::image::view::{ImageView, ImageViewCreateInfo, ImageViewType};
+use vulkano::image::{
+    AllocateImageError, Image, ImageCreateFlags, ImageCreateInfo, ImageFormatInfo, ImageType,
+    ImageUsage, SampleCount,
+};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+use vulkano::{Validated, VulkanError};
+
+use crate::vk_memory::{track_image, MemoryCategory};
+use crate::vk_utils::Vk;
+
+/// Everything needed to create an image, built up with chained setters:
+///
+/// ```ignore
+/// let (image, view) = vk.create_image(
+///     &ImageDesc::new_2d([512, 512])
+///         .format(Format::R16G16B16A16_SFLOAT)
+///         .full_mip_chain()
+///         .usage(ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST),
+/// ).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ImageDesc {
+    pub image_type: ImageType,
+    pub format: Format,
+    pub extent: [u32; 3],
+    pub array_layers: u32,
+    pub mip_levels: u32,
+    pub samples: SampleCount,
+    pub usage: ImageUsage,
+    pub cube_compatible: bool,
+    pub memory_type_filter: MemoryTypeFilter,
+}
+
+impl ImageDesc {
+    fn new(image_type: ImageType, extent: [u32; 3]) -> Self {
+        Self {
+            image_type,
+            format: Format::R8G8B8A8_UNORM,
+            extent,
+            array_layers: 1,
+            mip_levels: 1,
+            samples: SampleCount::Sample1,
+            usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+            cube_compatible: false,
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+        }
+    }
+
+    pub fn new_1d(width: u32) -> Self {
+        Self::new(ImageType::Dim1d, [width, 1, 1])
+    }
+
+    pub fn new_2d([width, height]: [u32; 2]) -> Self {
+        Self::new(ImageType::Dim2d, [width, height, 1])
+    }
+
+    pub fn new_3d(extent: [u32; 3]) -> Self {
+        Self::new(ImageType::Dim3d, extent)
+    }
+
+    /// Six square 2D layers per cube, viewed as a cube (or cube array for `cubes > 1`).
+    pub fn new_cube(size: u32, cubes: u32) -> Self {
+        Self {
+            array_layers: 6 * cubes,
+            cube_compatible: true,
+            ..Self::new(ImageType::Dim2d, [size, size, 1])
+        }
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn array_layers(mut self, array_layers: u32) -> Self {
+        self.array_layers = array_layers;
+        self
+    }
+
+    pub fn mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
+    /// Mip levels all the way down to 1x1.
+    pub fn full_mip_chain(mut self) -> Self {
+        self.mip_levels = self.extent.into_iter().max().unwrap().max(1).ilog2() + 1;
+        self
+    }
+
+    pub fn samples(mut self, samples: SampleCount) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn usage(mut self, usage: ImageUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn memory(mut self, memory_type_filter: MemoryTypeFilter) -> Self {
+        self.memory_type_filter = memory_type_filter;
+        self
+    }
+
+    fn flags(&self) -> ImageCreateFlags {
+        if self.cube_compatible {
+            ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            ImageCreateFlags::empty()
+        }
+    }
+
+    fn view_type(&self) -> ImageViewType {
+        match (self.image_type, self.cube_compatible, self.array_layers) {
+            (ImageType::Dim1d, _, 1) => ImageViewType::Dim1d,
+            (ImageType::Dim1d, _, _) => ImageViewType::Dim1dArray,
+            (ImageType::Dim3d, _, _) => ImageViewType::Dim3d,
+            (_, true, 6) => ImageViewType::Cube,
+            (_, true, _) => ImageViewType::CubeArray,
+            (_, false, 1) => ImageViewType::Dim2d,
+            (_, false, _) => ImageViewType::Dim2dArray,
+        }
+    }
+
+    /// Combinations that no device can create, whatever its format support.
+    fn validate(&self) -> Result<(), ImageDescError> {
+        if self.samples != SampleCount::Sample1
+            && (self.mip_levels > 1 || self.image_type != ImageType::Dim2d || self.cube_compatible)
+        {
+            return Err(ImageDescError::Invalid("multisampled images need one mip level and a plain 2D type"));
+        }
+        if self.cube_compatible {
+            if self.array_layers == 0 || !self.array_layers.is_multiple_of(6) {
+                return Err(ImageDescError::Invalid("cube images need a multiple of 6 layers"));
+            }
+            if self.extent[0] != self.extent[1] {
+                return Err(ImageDescError::Invalid("cube faces need to be square"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reasons an `ImageDesc` can not be created on the current device.
+#[derive(Clone, Debug)]
+pub enum ImageDescError {
+    /// The format lacks features that the requested usage needs.
+    MissingFormatFeatures { format: Format, missing: FormatFeatures },
+    /// The device can not create this format / type / usage combination at all.
+    UnsupportedCombination,
+    /// The combination is supported, but not with this extent, layer, mip or sample count.
+    ExceedsLimits(&'static str),
+    /// The settings contradict each other on any device.
+    Invalid(&'static str),
+    /// The view type needs a device feature that is not enabled.
+    MissingFeature(&'static str),
+    /// Creating the image itself failed.
+    Image(Validated<AllocateImageError>),
+    /// Creating the view of the image failed.
+    View(Validated<VulkanError>),
+}
+
+impl fmt::Display for ImageDescError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingFormatFeatures { format, missing } => {
+                write!(f, "{format:?} does not support {missing:?}")
+            }
+            Self::UnsupportedCombination => write!(f, "unsupported image format/type/usage"),
+            Self::ExceedsLimits(what) => write!(f, "image {what} exceeds device limits"),
+            Self::Invalid(why) => write!(f, "invalid image: {why}"),
+            Self::MissingFeature(feature) => write!(f, "image view needs the {feature} feature"),
+            Self::Image(e) => write!(f, "failed to create image: {e}"),
+            Self::View(e) => write!(f, "failed to create image view: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ImageDescError {}
+
+fn required_features(usage: ImageUsage) -> FormatFeatures {
+    let mut features = FormatFeatures::empty();
+    let pairs = [
+        (ImageUsage::SAMPLED, FormatFeatures::SAMPLED_IMAGE),
+        (ImageUsage::STORAGE, FormatFeatures::STORAGE_IMAGE),
+        (ImageUsage::COLOR_ATTACHMENT, FormatFeatures::COLOR_ATTACHMENT),
+        (ImageUsage::DEPTH_STENCIL_ATTACHMENT, FormatFeatures::DEPTH_STENCIL_ATTACHMENT),
+        (ImageUsage::TRANSFER_SRC, FormatFeatures::TRANSFER_SRC),
+        (ImageUsage::TRANSFER_DST, FormatFeatures::TRANSFER_DST),
+    ];
+
+    for (image_usage, feature) in pairs {
+        if usage.intersects(image_usage) {
+            features |= feature;
+        }
+    }
+
+    features
+}
+
+impl Vk {
+    /// Checks `desc` against the device's format properties and limits, then creates the image
+    /// along with a view of every layer and mip level.
+    pub fn create_image(&self, desc: &ImageDesc) -> Result<(Arc<Image>, Arc<ImageView>), ImageDescError> {
+        desc.validate()?;
+        if desc.view_type() == ImageViewType::CubeArray && !self.device.enabled_features().image_cube_array {
+            return Err(ImageDescError::MissingFeature("image_cube_array"));
+        }
+
+        let features = self.physical_device
+            .format_properties(desc.format)
+            .unwrap()
+            .optimal_tiling_features;
+        let missing = required_features(desc.usage) - features;
+        if !missing.is_empty() {
+            return Err(ImageDescError::MissingFormatFeatures { format: desc.format, missing });
+        }
+
+        let properties = self.physical_device
+            .image_format_properties(ImageFormatInfo {
+                flags: desc.flags(),
+                format: desc.format,
+                image_type: desc.image_type,
+                usage: desc.usage,
+                ..Default::default()
+            })
+            .unwrap()
+            .ok_or(ImageDescError::UnsupportedCombination)?;
+
+        if desc.extent.iter().zip(properties.max_extent).any(|(e, max)| *e > max) {
+            return Err(ImageDescError::ExceedsLimits("extent"));
+        }
+        if desc.array_layers > properties.max_array_layers {
+            return Err(ImageDescError::ExceedsLimits("array layer count"));
+        }
+        if desc.mip_levels > properties.max_mip_levels {
+            return Err(ImageDescError::ExceedsLimits("mip level count"));
+        }
+        if !properties.sample_counts.contains_enum(desc.samples) {
+            return Err(ImageDescError::ExceedsLimits("sample count"));
+        }
+
+        let image = Image::new(
+            self.mem_allocators.memory_allocator.clone(),
+            ImageCreateInfo {
+                flags: desc.flags(),
+                image_type: desc.image_type,
+                format: desc.format,
+                extent: desc.extent,
+                array_layers: desc.array_layers,
+                mip_levels: desc.mip_levels,
+                samples: desc.samples,
+                usage: desc.usage,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: desc.memory_type_filter,
+                ..Default::default()
+            },
+        )
+        .map_err(ImageDescError::Image)?;
+        track_image(MemoryCategory::Image, &image);
+
+        let view = ImageView::new(
+            image.clone(),
+            ImageViewCreateInfo {
+                view_type: desc.view_type(),
+                ..ImageViewCreateInfo::from_image(&image)
+            },
+        )
+        .map_err(ImageDescError::View)?;
+
+        Ok((image, view))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multisampling_needs_a_single_2d_level() {
+        let msaa = ImageDesc::new_2d([64, 64]).samples(SampleCount::Sample4);
+        assert!(msaa.validate().is_ok());
+        assert!(msaa.clone().mip_levels(2).validate().is_err());
+        assert!(ImageDesc::new_3d([8, 8, 8]).samples(SampleCount::Sample4).validate().is_err());
+        assert!(ImageDesc::new_cube(64, 1).samples(SampleCount::Sample4).validate().is_err());
+    }
+
+    #[test]
+    fn cubes_need_whole_cubes_of_layers() {
+        assert!(ImageDesc::new_cube(64, 2).validate().is_ok());
+        assert!(ImageDesc::new_cube(64, 1).array_layers(8).validate().is_err());
+        assert!(ImageDesc::new_cube(64, 0).validate().is_err());
+    }
+
+    #[test]
+    fn view_types_follow_the_layers() {
+        assert_eq!(ImageDesc::new_cube(64, 1).view_type(), ImageViewType::Cube);
+        assert_eq!(ImageDesc::new_cube(64, 3).view_type(), ImageViewType::CubeArray);
+        assert_eq!(ImageDesc::new_2d([4, 4]).array_layers(2).view_type(), ImageViewType::Dim2dArray);
+    }
+}
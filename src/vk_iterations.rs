@@ -0,0 +1,402 @@
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::format::Format;
+use vulkano::image::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
+use vulkano::image::ImageUsage;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+use vulkano::pipeline::compute::ComputePipelineCreateInfo;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::{
+    ComputePipeline, GraphicsPipeline, Pipeline, PipelineBindPoint, PipelineLayout,
+    PipelineShaderStageCreateInfo,
+};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass};
+use vulkano::shader::{ShaderModule, SpecializationConstant};
+
+use crate::distance::DistanceMode;
+use crate::fractal::FractalKind;
+use crate::orbit_trap::TrapMode;
+use crate::vk_image::ImageDesc;
+use crate::vk_memory::{track_buffer, MemoryCategory};
+use crate::vk_pipeline::record_pass;
+use crate::vk_present::{descriptor_set, fs, VkView};
+use crate::vk_utils::Vk;
+
+pub mod histogram_cs {
+    vulkano_shaders::shader!{
+        ty: "compute",
+        path: "src/shaders/histogram.comp",
+    }
+}
+
+/// Bins of the histogram of iteration counts that histogram equalization evens out. The
+/// shaders take the count from the length of their buffers.
+pub const HISTOGRAM_BINS: u64 = 1024;
+
+/// Two-pass rendering: the fractal shader, specialized with `ITERATIONS`, writes the features
+/// of every pixel's orbit (see `colour.glsl`) into an image the size of the window, and a
+/// colouring pass draws that image to the swapchain. The first pass only runs again when
+/// something it reads changes, so palette changes, palette cycling and the outline and light
+/// settings skip the iteration entirely.
+///
+/// With `equalize` set the colouring pass spreads the palette by a histogram of the iteration
+/// counts instead of by the counts themselves. The histogram is counted on the GPU whenever the
+/// image is iterated, then read back to sum it up, which costs a wait on the GPU; colouring
+/// alone costs nothing more.
+///
+/// The image holds one sample per pixel, so this is only used without supersampling. Every
+/// swapchain image has one of its own: the frame drawn to an image has completed before the
+/// next frame drawn to it is recorded, so its iteration pass never races a colouring pass.
+/// The pipelines are shared, see `IterationPipelines`.
+pub struct IterationBuffer {
+    /// `R32_SFLOAT` for the iteration count alone, four channels with the trap and distance.
+    pub format: Format,
+    /// Holds the image, which has no other owner.
+    pub framebuffer: Arc<Framebuffer>,
+    pub descriptor_set: Arc<PersistentDescriptorSet>,
+    pub colour_descriptor_set: Arc<PersistentDescriptorSet>,
+    pub histogram: Subbuffer<[u32]>,
+    pub histogram_descriptor_set: Arc<PersistentDescriptorSet>,
+    /// `equalization_cdf` of `histogram`, read by the colouring pass.
+    pub cdf: Subbuffer<[f32]>,
+    /// `iteration_key` of the push constants the image was last rendered with.
+    pub rendered: Option<fs::PushConstantData>,
+    /// `iteration_key` of the push constants `cdf` was counted for.
+    pub equalized: Option<fs::PushConstantData>,
+}
+
+/// Pipelines of both passes for every iteration buffer format, and the sampler the colouring
+/// pass reads the iteration buffer with. Rebuilt with the view's pipeline, since they depend on
+/// the same viewport, fractal kind and precision.
+pub struct IterationPipelines {
+    /// One iteration pass and render pass per format of `IterationBuffer::FORMATS`.
+    pub iterate: Vec<Arc<GraphicsPipeline>>,
+    pub render_passes: Vec<Arc<RenderPass>>,
+    pub colour: Arc<GraphicsPipeline>,
+    pub histogram: Arc<ComputePipeline>,
+    pub sampler: Arc<Sampler>,
+}
+
+impl IterationPipelines {
+    pub fn new(
+        vk: &Vk,
+        vs: &Arc<ShaderModule>,
+        fs: &Arc<ShaderModule>,
+        colour_fs: &Arc<ShaderModule>,
+        kind: FractalKind,
+        render_pass: &Arc<RenderPass>,
+        viewport: &Viewport,
+    ) -> Self {
+        let mut constants: Vec<_> = kind.specialization();
+        constants.push((1, SpecializationConstant::Bool(true)));
+        let iterate_fs = fs.specialize(constants.into_iter().collect()).unwrap();
+        let render_passes: Vec<_> = IterationBuffer::FORMATS
+            .iter()
+            .map(|format| vk.get_render_pass_with_format(*format))
+            .collect();
+        let iterate = render_passes
+            .iter()
+            .map(|render_pass| {
+                vk.get_pipeline(
+                    vs.entry_point("main").unwrap(),
+                    iterate_fs.entry_point("main").unwrap(),
+                    render_pass.clone(),
+                    viewport.clone(),
+                )
+                .0
+            })
+            .collect();
+
+        let (colour, _) = vk.get_pipeline(
+            vs.entry_point("main").unwrap(),
+            colour_fs.specialize(kind.specialization()).unwrap().entry_point("main").unwrap(),
+            render_pass.clone(),
+            viewport.clone(),
+        );
+
+        let stage = PipelineShaderStageCreateInfo::new(
+            histogram_cs::load(vk.device.clone()).unwrap().entry_point("main").unwrap(),
+        );
+        let layout = PipelineLayout::new(
+            vk.device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+                .into_pipeline_layout_create_info(vk.device.clone())
+                .unwrap(),
+        )
+        .unwrap();
+        let histogram = ComputePipeline::new(
+            vk.device.clone(),
+            None,
+            ComputePipelineCreateInfo::stage_layout(stage, layout),
+        )
+        .unwrap();
+
+        Self {
+            iterate,
+            render_passes,
+            colour,
+            histogram,
+            // read with texelFetch, the sampler is never used to filter
+            sampler: Sampler::new(vk.device.clone(), SamplerCreateInfo::default()).unwrap(),
+        }
+    }
+
+    /// Iteration pass drawing into an image of `format`.
+    pub fn iterate(&self, format: Format) -> &Arc<GraphicsPipeline> {
+        &self.iterate[Self::index(format)]
+    }
+
+    pub fn render_pass(&self, format: Format) -> &Arc<RenderPass> {
+        &self.render_passes[Self::index(format)]
+    }
+
+    fn index(format: Format) -> usize {
+        IterationBuffer::FORMATS.iter().position(|f| *f == format).unwrap()
+    }
+}
+
+impl IterationBuffer {
+    pub const FORMATS: [Format; 2] = [Format::R32_SFLOAT, Format::R32G32B32A32_SFLOAT];
+
+    /// Image format holding what colouring with `pc` needs.
+    pub fn format_for(pc: &fs::PushConstantData) -> Format {
+        if pc.trap_mode == TrapMode::Off as u32 && pc.distance_mode == DistanceMode::Off as u32 {
+            Self::FORMATS[0]
+        } else {
+            Self::FORMATS[1]
+        }
+    }
+
+    /// Creates the image for `view`'s viewport and its descriptor sets for `view`'s pipelines.
+    pub fn new(vk: &Vk, view: &VkView, format: Format) -> Self {
+        let extent = [view.viewport.extent[0] as u32, view.viewport.extent[1] as u32];
+        let (_, image_view) = vk
+            .create_image(
+                &ImageDesc::new_2d(extent)
+                    .format(format)
+                    .usage(ImageUsage::COLOR_ATTACHMENT | ImageUsage::SAMPLED),
+            )
+            .unwrap_or_else(|e| panic!("failed to create {extent:?} iteration buffer: {e}"));
+
+        let pipelines = &view.iteration_pipelines;
+        let framebuffer = Framebuffer::new(
+            pipelines.render_pass(format).clone(),
+            FramebufferCreateInfo {
+                attachments: vec![image_view.clone()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let histogram = storage_buffer::<u32>(
+            vk,
+            BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+            MemoryTypeFilter::PREFER_DEVICE,
+            HISTOGRAM_BINS,
+        );
+        let cdf = storage_buffer::<f32>(
+            vk,
+            BufferUsage::empty(),
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            HISTOGRAM_BINS + 1,
+        );
+
+        let descriptor_set = descriptor_set(vk, pipelines.iterate(format).layout(), &view.palette(), &view.reference_orbit());
+        let colour_descriptor_set = colour_descriptor_set(vk, pipelines, view, image_view.clone(), cdf.clone());
+        let histogram_descriptor_set = PersistentDescriptorSet::new(
+            &vk.mem_allocators.descriptor_set_allocator,
+            pipelines.histogram.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(0, image_view, pipelines.sampler.clone()),
+                WriteDescriptorSet::buffer(1, histogram.clone()),
+            ],
+            [],
+        )
+        .unwrap();
+
+        Self {
+            format,
+            framebuffer,
+            descriptor_set,
+            colour_descriptor_set,
+            histogram,
+            histogram_descriptor_set,
+            cdf,
+            rendered: None,
+            equalized: None,
+        }
+    }
+
+    /// Counts the histogram of the image and sums it up into `cdf` for the colouring pass,
+    /// iterating into the image first when `iterate` is set. Waits for the GPU.
+    pub fn equalize(&mut self, vk: &Vk, view: &VkView, iterate: bool) {
+        let pipelines = &view.iteration_pipelines;
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &vk.mem_allocators.command_buffer_allocator,
+            vk.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        if iterate {
+            record_pass(
+                &mut builder,
+                pipelines.iterate(self.format),
+                &self.framebuffer,
+                &view.vert_buffer,
+                self.descriptor_set.clone(),
+                view.push_constants,
+            );
+        }
+        let [width, height] = self.framebuffer.extent();
+        builder
+            .fill_buffer(self.histogram.clone(), 0)
+            .unwrap()
+            .bind_pipeline_compute(pipelines.histogram.clone())
+            .unwrap()
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                pipelines.histogram.layout().clone(),
+                0,
+                self.histogram_descriptor_set.clone(),
+            )
+            .unwrap()
+            .dispatch([width.div_ceil(16), height.div_ceil(16), 1])
+            .unwrap();
+        vk.sync(builder.build().unwrap());
+
+        let cdf = equalization_cdf(&vk.read_buffer(self.histogram.clone()));
+        self.cdf.write().unwrap().copy_from_slice(&cdf);
+
+        let key = iteration_key(&view.push_constants);
+        self.rendered = Some(key);
+        self.equalized = Some(key);
+    }
+
+    /// Points the first pass at a new reference orbit, which it has to run again for.
+    pub fn set_reference_orbit(&mut self, vk: &Vk, view: &VkView) {
+        let layout = view.iteration_pipelines.iterate(self.format).layout();
+        self.descriptor_set = descriptor_set(vk, layout, &view.palette(), &view.reference_orbit());
+        self.rendered = None;
+    }
+
+    /// Colours the image into `framebuffer`, iterating into it first when `iterate` is set.
+    pub fn command_buffer(
+        &self,
+        vk: &Vk,
+        view: &VkView,
+        framebuffer: &Arc<Framebuffer>,
+        iterate: bool,
+    ) -> Arc<PrimaryAutoCommandBuffer> {
+        let pipelines = &view.iteration_pipelines;
+        let iteration_pass = (pipelines.iterate(self.format), &self.framebuffer, self.descriptor_set.clone());
+        let colour_pass = (&pipelines.colour, framebuffer, self.colour_descriptor_set.clone());
+        let passes = if iterate { vec![iteration_pass, colour_pass] } else { vec![colour_pass] };
+        vk.get_command_buffer_passes(&passes, &view.vert_buffer, view.push_constants)
+    }
+}
+
+/// `pc` without what only the colouring pass reads, so push constants with equal keys iterate
+/// to the same image.
+pub fn iteration_key(pc: &fs::PushConstantData) -> fs::PushConstantData {
+    fs::PushConstantData {
+        palette_index: 0,
+        palette_offset: 0.0,
+        palette_scale: 0.0,
+        distance_width: 0.0,
+        light_angle: 0.0,
+        light_height: 0.0,
+        equalize: 0,
+        ..*pc
+    }
+}
+
+/// Share of the pixels counted in `histogram` below each of its `histogram.len() + 1` bin
+/// edges, rising from 0 to 1. All 0 when nothing was counted.
+pub fn equalization_cdf(histogram: &[u32]) -> Vec<f32> {
+    let total = histogram.iter().map(|n| *n as u64).sum::<u64>().max(1);
+    let below = histogram.iter().scan(0u64, |below, n| {
+        *below += *n as u64;
+        Some((*below as f64 / total as f64) as f32)
+    });
+    std::iter::once(0.0).chain(below).collect()
+}
+
+fn storage_buffer<T: vulkano::buffer::BufferContents>(
+    vk: &Vk,
+    usage: BufferUsage,
+    memory_type_filter: MemoryTypeFilter,
+    len: u64,
+) -> Subbuffer<[T]> {
+    let buffer = Buffer::new_slice::<T>(
+        vk.mem_allocators.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage: usage | BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter,
+            ..Default::default()
+        },
+        len,
+    )
+    .expect("failed to create storage buffer");
+
+    track_buffer(MemoryCategory::Other, buffer.buffer());
+    buffer
+}
+
+fn colour_descriptor_set(
+    vk: &Vk,
+    pipelines: &IterationPipelines,
+    view: &VkView,
+    iterations: Arc<ImageView>,
+    cdf: Subbuffer<[f32]>,
+) -> Arc<PersistentDescriptorSet> {
+    let palette = view.palette();
+    PersistentDescriptorSet::new(
+        &vk.mem_allocators.descriptor_set_allocator,
+        pipelines.colour.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::image_view_sampler(0, palette.view, palette.sampler),
+            WriteDescriptorSet::image_view_sampler(1, iterations, pipelines.sampler.clone()),
+            WriteDescriptorSet::buffer(2, cdf),
+        ],
+        [],
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn even_histograms_equalize_to_a_straight_line() {
+        assert_eq!(equalization_cdf(&[5, 5, 5, 5]), vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn crowded_bins_get_more_of_the_palette() {
+        let cdf = equalization_cdf(&[1, 8, 0, 1]);
+        assert_eq!(cdf, vec![0.0, 0.1, 0.9, 0.9, 1.0]);
+    }
+
+    #[test]
+    fn empty_histograms_equalize_to_zero() {
+        assert_eq!(equalization_cdf(&[0, 0, 0]), vec![0.0; 4]);
+    }
+
+    #[test]
+    fn equalizing_does_not_change_the_iteration_key() {
+        let mut pc = *crate::vk_present::FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+        let key = iteration_key(&pc);
+        pc.equalize = 1;
+        assert_eq!(iteration_key(&pc), key);
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+use std::sync::{Arc, Mutex, Weak};
+
+use once_cell::sync::Lazy;
+
+use vulkano::buffer::{Buffer, BufferMemory};
+use vulkano::image::{Image, ImageMemory};
+use vulkano::memory::{MemoryHeapFlags, ResourceMemory};
+use vulkano::{Version, VulkanObject};
+
+use crate::vk_utils::Vk;
+
+/// What an allocation is used for, so usage can be broken down in the frame stats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryCategory {
+    Vertex,
+    Image,
+    Staging,
+    Uniform,
+    Readback,
+    Other,
+}
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; 6] = [
+        MemoryCategory::Vertex,
+        MemoryCategory::Image,
+        MemoryCategory::Staging,
+        MemoryCategory::Uniform,
+        MemoryCategory::Readback,
+        MemoryCategory::Other,
+    ];
+}
+
+/// Keeps weak references to every tracked buffer and image; whatever has been dropped since
+/// the last `stats` call is no longer counted, so anything that keeps growing is a leak.
+pub struct MemoryTracker {
+    pub buffers: Vec<(MemoryCategory, Weak<Buffer>)>,
+    pub images: Vec<(MemoryCategory, Weak<Image>)>,
+}
+
+pub static MEMORY_TRACKER: Lazy<Mutex<MemoryTracker>> = Lazy::new(|| {
+    Mutex::new(MemoryTracker {
+        buffers: vec![],
+        images: vec![],
+    })
+});
+
+pub fn track_buffer(category: MemoryCategory, buffer: &Arc<Buffer>) {
+    MEMORY_TRACKER.lock().unwrap().buffers.push((category, Arc::downgrade(buffer)));
+}
+
+pub fn track_image(category: MemoryCategory, image: &Arc<Image>) {
+    MEMORY_TRACKER.lock().unwrap().images.push((category, Arc::downgrade(image)));
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CategoryUsage {
+    pub count: usize,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapUsage {
+    pub size: u64,
+    pub device_local: bool,
+    /// Bytes of this heap used by tracked resources.
+    pub tracked: u64,
+    /// Usage and budget of the whole process as reported by `VK_EXT_memory_budget`.
+    pub usage: Option<u64>,
+    pub budget: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryStats {
+    pub categories: Vec<(MemoryCategory, CategoryUsage)>,
+    pub heaps: Vec<HeapUsage>,
+}
+
+impl MemoryTracker {
+    pub fn stats(&mut self, vk: &Vk) -> MemoryStats {
+        self.buffers.retain(|(_, buffer)| buffer.strong_count() > 0);
+        self.images.retain(|(_, image)| image.strong_count() > 0);
+
+        let memory_properties = vk.physical_device.memory_properties();
+        let budget = memory_budget(vk);
+
+        let mut categories: Vec<_> = MemoryCategory::ALL
+            .into_iter()
+            .map(|category| (category, CategoryUsage::default()))
+            .collect();
+        let mut heaps: Vec<_> = memory_properties.memory_heaps
+            .iter()
+            .enumerate()
+            .map(|(i, heap)| HeapUsage {
+                size: heap.size,
+                device_local: heap.flags.intersects(MemoryHeapFlags::DEVICE_LOCAL),
+                tracked: 0,
+                usage: budget.map(|b| b.heap_usage[i]),
+                budget: budget.map(|b| b.heap_budget[i]),
+            })
+            .collect();
+
+        let mut add = |category: MemoryCategory, memory: &[ResourceMemory]| {
+            let usage = &mut categories
+                .iter_mut()
+                .find(|(c, _)| *c == category)
+                .unwrap()
+                .1;
+            usage.count += 1;
+
+            for memory in memory {
+                let memory_type = memory.device_memory().memory_type_index() as usize;
+                let heap = memory_properties.memory_types[memory_type].heap_index as usize;
+
+                usage.bytes += memory.size();
+                heaps[heap].tracked += memory.size();
+            }
+        };
+
+        for (category, buffer) in &self.buffers {
+            if let Some(buffer) = buffer.upgrade() {
+                if let BufferMemory::Normal(memory) = buffer.memory() {
+                    add(*category, std::slice::from_ref(memory));
+                }
+            }
+        }
+        for (category, image) in &self.images {
+            if let Some(image) = image.upgrade() {
+                if let ImageMemory::Normal(memory) = image.memory() {
+                    add(*category, memory);
+                }
+            }
+        }
+
+        MemoryStats {
+            categories,
+            heaps,
+        }
+    }
+}
+
+/// Queries `VK_EXT_memory_budget`, if it was enabled on the device.
+fn memory_budget(vk: &Vk) -> Option<ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT> {
+    if !vk.device.enabled_extensions().ext_memory_budget
+        || vk.instance.api_version() < Version::V1_1
+    {
+        return None;
+    }
+
+    let mut budget = ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    let mut properties = ash::vk::PhysicalDeviceMemoryProperties2 {
+        p_next: &mut budget as *mut _ as *mut std::ffi::c_void,
+        ..Default::default()
+    };
+
+    unsafe {
+        (vk.instance.fns().v1_1.get_physical_device_memory_properties2)(
+            vk.physical_device.handle(),
+            &mut properties,
+        );
+    }
+
+    Some(budget)
+}
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MEMORY:")?;
+        for (category, usage) in &self.categories {
+            if usage.count > 0 {
+                write!(f, " {:?} {} ({:.1} MiB)", category, usage.count, usage.bytes as f64 / MIB)?;
+            }
+        }
+
+        for (i, heap) in self.heaps.iter().enumerate() {
+            write!(
+                f,
+                "\n    heap {i}{}: tracked {:.1} MiB",
+                if heap.device_local { " (device local)" } else { "" },
+                heap.tracked as f64 / MIB,
+            )?;
+            match (heap.usage, heap.budget) {
+                (Some(usage), Some(budget)) => write!(
+                    f,
+                    ", process {:.1} / {:.1} MiB budget",
+                    usage as f64 / MIB,
+                    budget as f64 / MIB,
+                )?,
+                _ => write!(f, " of {:.1} MiB", heap.size as f64 / MIB)?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::image::{Image, ImageUsage};
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo};
+
+use crate::fractal::Precision;
+use crate::vk_image::ImageDesc;
+use crate::vk_present::{descriptor_set, fs, VkView};
+use crate::vk_utils::Vk;
+
+/// Draws the fractal of a `VkView` into an image of its own instead of the swapchain, for
+/// exports that are larger than the window or need frames the window never shows. The image
+/// has the swapchain's format, so the colours come out exactly as on screen.
+pub struct Offscreen {
+    pub extent: [u32; 2],
+    pub image: Arc<Image>,
+    pub framebuffer: Arc<Framebuffer>,
+    pub pipeline: Arc<GraphicsPipeline>,
+    pub descriptor_set: Arc<PersistentDescriptorSet>,
+}
+
+impl Offscreen {
+    /// Largest square that fits the device's image and framebuffer limits, capped at `max`.
+    pub fn max_extent(vk: &Vk, max: u32) -> u32 {
+        let limits = vk.physical_device.properties();
+        max.min(limits.max_image_dimension2_d)
+            .min(limits.max_framebuffer_width)
+            .min(limits.max_framebuffer_height)
+    }
+
+    pub fn new(vk: &Vk, view: &VkView, extent: [u32; 2], precision: Precision) -> Self {
+        let render_pass = vk.get_render_pass();
+        let format = vk.swapchain.clone().unwrap().image_format();
+        let (image, image_view) = vk
+            .create_image(
+                &ImageDesc::new_2d(extent)
+                    .format(format)
+                    .usage(ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC),
+            )
+            .unwrap_or_else(|e| panic!("failed to create {extent:?} offscreen image: {e}"));
+
+        let framebuffer = Framebuffer::new(
+            render_pass.clone(),
+            FramebufferCreateInfo {
+                attachments: vec![image_view],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let (pipeline, layout) = vk.get_pipeline(
+            view.shader_mods[0].entry_point("main").unwrap(),
+            view.shader_mods[1 + precision as usize]
+                .specialize(view.kind.specialization())
+                .unwrap()
+                .entry_point("main")
+                .unwrap(),
+            render_pass,
+            Viewport {
+                offset: [0.0, 0.0],
+                extent: [extent[0] as f32, extent[1] as f32],
+                depth_range: 0.0..=1.0,
+            },
+        );
+        let descriptor_set = descriptor_set(vk, &layout, &view.palette(), &view.reference_orbit());
+
+        Self {
+            extent,
+            image,
+            framebuffer,
+            pipeline,
+            descriptor_set,
+        }
+    }
+
+    /// Draws with `pc`, its `ires` replaced by the image extent, and reads the result back,
+    /// blocking until both are done.
+    pub fn render(&self, vk: &Vk, view: &VkView, mut pc: fs::PushConstantData) -> image::RgbaImage {
+        pc.ires = self.extent.map(|e| e as f32);
+        let command_buffer = vk.get_command_buffer(
+            &self.pipeline,
+            &self.framebuffer,
+            &view.vert_buffer,
+            self.descriptor_set.clone(),
+            pc,
+        );
+        vk.sync(command_buffer);
+
+        vk.read_image(self.image.clone())
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::Arc;
+use std::sync::mpsc;
+
+use threadpool::ThreadPool;
+
+use vulkano::buffer::Subbuffer;
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferInheritanceInfo, CommandBufferInheritanceRenderPassInfo,
+    CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo, SecondaryAutoCommandBuffer,
+    SecondaryCommandBufferAbstract, SubpassBeginInfo, SubpassContents,
+};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, Subpass};
+
+use crate::vk_pipeline::{vert, FVertex3d};
+use crate::vk_utils::Vk;
+
+pub type SecondaryCommandBuffer = SecondaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>;
+
+/// Records secondary command buffers for independent draw batches on a thread pool.
+/// Every worker job gets its own command buffer allocator so recording never contends on one.
+pub struct VkRecorder {
+    pub pool: ThreadPool,
+    pub allocators: Vec<Arc<StandardCommandBufferAllocator>>,
+}
+
+impl VkRecorder {
+    pub fn new(vk: &Vk, pool: ThreadPool) -> Self {
+        let allocators = (0..pool.max_count())
+            .map(|_| {
+                Arc::new(StandardCommandBufferAllocator::new(
+                    vk.device.clone(),
+                    Default::default(),
+                ))
+            })
+            .collect();
+
+        Self {
+            pool,
+            allocators,
+        }
+    }
+
+    /// Records one secondary command buffer per batch. Batches are split into contiguous chunks,
+    /// one per worker, and the result is always returned in batch order no matter which worker
+    /// finishes first.
+    pub fn record_batches(
+        &self,
+        queue_family_index: u32,
+        pipeline: &Arc<GraphicsPipeline>,
+        framebuffer: &Arc<Framebuffer>,
+        batches: &[Subbuffer<[FVertex3d]>],
+        descriptor_set: Arc<PersistentDescriptorSet>,
+        push_constant: crate::vk_present::fs::PushConstantData,
+    ) -> Vec<Arc<SecondaryCommandBuffer>> {
+        if batches.is_empty() {
+            return vec![];
+        }
+
+        let chunk_size = batches.len().div_ceil(self.allocators.len());
+        let chunks = batches.chunks(chunk_size).count();
+        let (tx, rx) = mpsc::channel();
+
+        for (chunk_i, chunk) in batches.chunks(chunk_size).enumerate() {
+            let tx = tx.clone();
+            let allocator = self.allocators[chunk_i].clone();
+            let chunk = chunk.to_vec();
+            let pipeline = pipeline.clone();
+            let framebuffer = framebuffer.clone();
+            let descriptor_set = descriptor_set.clone();
+
+            self.pool.execute(move || {
+                let command_buffers = chunk
+                    .iter()
+                    .map(|vertex_buffer| {
+                        record_secondary(
+                            &allocator,
+                            queue_family_index,
+                            &pipeline,
+                            &framebuffer,
+                            vertex_buffer,
+                            descriptor_set.clone(),
+                            push_constant,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                tx.send((chunk_i, command_buffers)).unwrap();
+            });
+        }
+
+        let mut recorded: Vec<Option<Vec<Arc<SecondaryCommandBuffer>>>> = vec![None; chunks];
+        for _ in 0..chunks {
+            let (chunk_i, command_buffers) = rx.recv().expect("recording worker panicked");
+            recorded[chunk_i] = Some(command_buffers);
+        }
+
+        recorded.into_iter().flat_map(Option::unwrap).collect()
+    }
+}
+
+/// Two triangles covering horizontal strip `i` of `n` equal strips of the viewport, so that
+/// drawing all `n` covers it once.
+pub fn strip(i: u32, n: u32) -> [FVertex3d; 6] {
+    let top = -1.0 + 2.0 * i as f32 / n as f32;
+    let bottom = -1.0 + 2.0 * (i + 1) as f32 / n as f32;
+    [
+        vert(-1.0, top, 0.0),
+        vert(1.0, top, 0.0),
+        vert(-1.0, bottom, 0.0),
+        vert(-1.0, bottom, 0.0),
+        vert(1.0, top, 0.0),
+        vert(1.0, bottom, 0.0),
+    ]
+}
+
+fn record_secondary(
+    allocator: &Arc<StandardCommandBufferAllocator>,
+    queue_family_index: u32,
+    pipeline: &Arc<GraphicsPipeline>,
+    framebuffer: &Arc<Framebuffer>,
+    vertex_buffer: &Subbuffer<[FVertex3d]>,
+    descriptor_set: Arc<PersistentDescriptorSet>,
+    push_constant: crate::vk_present::fs::PushConstantData,
+) -> Arc<SecondaryCommandBuffer> {
+    let mut builder = AutoCommandBufferBuilder::secondary(
+        allocator,
+        queue_family_index,
+        CommandBufferUsage::MultipleSubmit,
+        CommandBufferInheritanceInfo {
+            render_pass: Some(
+                CommandBufferInheritanceRenderPassInfo {
+                    framebuffer: Some(framebuffer.clone()),
+                    ..CommandBufferInheritanceRenderPassInfo::subpass(
+                        Subpass::from(framebuffer.render_pass().clone(), 0).unwrap()
+                    )
+                }
+                .into(),
+            ),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    builder
+        .push_constants(pipeline.layout().clone(), 0, push_constant)
+        .unwrap()
+        .bind_pipeline_graphics(pipeline.clone())
+        .unwrap()
+        .bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            descriptor_set,
+        )
+        .unwrap()
+        .bind_vertex_buffers(0, vertex_buffer.clone())
+        .unwrap()
+        .draw(vertex_buffer.len() as u32, 1, 0, 0)
+        .unwrap();
+
+    builder.build().unwrap()
+}
+
+impl Vk {
+    /// Same as `get_command_buffer`, but every vertex buffer in `batches` is recorded into its
+    /// own secondary command buffer on `recorder`'s pool and executed in order.
+    pub fn get_command_buffer_parallel(
+        &self,
+        recorder: &VkRecorder,
+        pipeline: &Arc<GraphicsPipeline>,
+        framebuffer: &Arc<Framebuffer>,
+        batches: &[Subbuffer<[FVertex3d]>],
+        descriptor_set: Arc<PersistentDescriptorSet>,
+        push_constant: crate::vk_present::fs::PushConstantData,
+    ) -> Arc<PrimaryAutoCommandBuffer> {
+        let secondaries = recorder.record_batches(
+            self.queue.queue_family_index(),
+            pipeline,
+            framebuffer,
+            batches,
+            descriptor_set,
+            push_constant,
+        );
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.mem_allocators.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::MultipleSubmit,
+        )
+        .unwrap();
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 1.0, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+                },
+                SubpassBeginInfo {
+                    contents: SubpassContents::SecondaryCommandBuffers,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .execute_commands_from_vec(
+                secondaries
+                    .into_iter()
+                    .map(|cb| cb as Arc<dyn SecondaryCommandBufferAbstract>)
+                    .collect(),
+            )
+            .unwrap()
+            .end_render_pass(Default::default())
+            .unwrap();
+
+        builder.build().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_cover_the_viewport_once() {
+        let n = 5;
+        let mut previous_bottom = -1.0;
+        for i in 0..n {
+            let ys = strip(i, n).map(|v| v.position[1]);
+            let xs = strip(i, n).map(|v| v.position[0]);
+            let top = ys.iter().copied().fold(f32::MAX, f32::min);
+            let bottom = ys.iter().copied().fold(f32::MIN, f32::max);
+            assert_eq!(top, previous_bottom);
+            assert_eq!(xs.iter().copied().fold(f32::MAX, f32::min), -1.0);
+            assert_eq!(xs.iter().copied().fold(f32::MIN, f32::max), 1.0);
+            previous_bottom = bottom;
+        }
+        assert_eq!(previous_bottom, 1.0);
+    }
+}
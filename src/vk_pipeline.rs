@@ -5,6 +5,8 @@ use vulkano::command_buffer::{
     AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
     SubpassBeginInfo, SubpassContents,
 };
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::format::Format;
 use vulkano::image::view::ImageView;
 use vulkano::pipeline::graphics::color_blend::{ColorBlendAttachmentState, ColorBlendState};
 use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
@@ -14,17 +16,19 @@ use vulkano::pipeline::graphics::vertex_input::{Vertex, VertexDefinition};
 use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
 use vulkano::pipeline::graphics::GraphicsPipelineCreateInfo;
 use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
-use vulkano::pipeline::{GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo};
+use vulkano::pipeline::{
+    GraphicsPipeline, Pipeline as _, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo,
+};
 use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
-use vulkano::shader::ShaderModule;
+use vulkano::shader::EntryPoint;
 
 use crate::vk_utils::Vk;
 
 #[repr(C)]
-#[derive(BufferContents, Vertex)]
+#[derive(BufferContents, Vertex, Clone, Copy)]
 pub struct FVertex3d {
     #[format(R32G32B32_SFLOAT)]
-    position: [f32; 3],
+    pub position: [f32; 3],
 }
 
 pub fn vert(x: f32, y: f32, z: f32) -> FVertex3d {
@@ -43,12 +47,16 @@ pub struct Pipeline {
 
 impl Vk {
     pub fn get_render_pass(&self) -> Arc<RenderPass> {
+        // Set the format the same as the swapchain.
+        self.get_render_pass_with_format(self.swapchain.clone().unwrap().image_format())
+    }
+
+    pub fn get_render_pass_with_format(&self, format: Format) -> Arc<RenderPass> {
         vulkano::single_pass_renderpass!(
             self.device.clone(),
             attachments: {
                 color: {
-                    // Set the format the same as the swapchain.
-                    format: self.swapchain.clone().unwrap().image_format(),
+                    format: format,
                     samples: 1,
                     load_op: Clear,
                     store_op: Store,
@@ -86,14 +94,11 @@ impl Vk {
 
     pub fn get_pipeline(
         &self,
-        vs: Arc<ShaderModule>,
-        fs: Arc<ShaderModule>,
+        vs: EntryPoint,
+        fs: EntryPoint,
         render_pass: Arc<RenderPass>,
         viewport: Viewport,
     ) -> (Arc<GraphicsPipeline>, Arc<PipelineLayout>) {
-        let vs = vs.entry_point("main").unwrap();
-        let fs = fs.entry_point("main").unwrap();
-
         let vertex_input_state = FVertex3d::per_vertex()
             .definition(&vs.info().input_interface)
             .unwrap();
@@ -137,49 +142,80 @@ impl Vk {
         .unwrap(), layout)
     }
 
-    pub fn get_command_buffers(
+    pub fn get_command_buffer(
         &self,
         pipeline: &Arc<GraphicsPipeline>,
-        framebuffers: &[Arc<Framebuffer>],
+        framebuffer: &Arc<Framebuffer>,
         vertex_buffer: &Subbuffer<[FVertex3d]>,
-        layout: Arc<PipelineLayout>,
+        descriptor_set: Arc<PersistentDescriptorSet>,
         push_constant: crate::vk_present::fs::PushConstantData,
-    ) -> Vec<Arc<PrimaryAutoCommandBuffer>> {
-        framebuffers
-            .iter()
-            .map(|framebuffer| {
-                let mut builder = AutoCommandBufferBuilder::primary(
-                    &self.mem_allocators.command_buffer_allocator,
-                    self.queue.queue_family_index(),
-                    CommandBufferUsage::MultipleSubmit,
-                )
-                .unwrap();
-
-                builder
-                    .begin_render_pass(
-                        RenderPassBeginInfo {
-                            clear_values: vec![Some([0.0, 0.0, 1.0, 1.0].into())],
-                            ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
-                        },
-                        SubpassBeginInfo {
-                            contents: SubpassContents::Inline,
-                            ..Default::default()
-                        },
-                    )
-                    .unwrap()
-                    .push_constants(layout.clone(), 0, push_constant)
-                    .unwrap()
-                    .bind_pipeline_graphics(pipeline.clone())
-                    .unwrap()
-                    .bind_vertex_buffers(0, vertex_buffer.clone())
-                    .unwrap()
-                    .draw(vertex_buffer.len() as u32, 1, 0, 0)
-                    .unwrap()
-                    .end_render_pass(Default::default())
-                    .unwrap();
-
-                builder.build().unwrap()
-            })
-            .collect()
+    ) -> Arc<PrimaryAutoCommandBuffer> {
+        self.get_command_buffer_passes(
+            &[(pipeline, framebuffer, descriptor_set)],
+            vertex_buffer,
+            push_constant,
+        )
+    }
+
+    /// Records one draw per `(pipeline, framebuffer, descriptor set)` pass, in order, into a
+    /// single command buffer, so each pass can read what the ones before it wrote.
+    pub fn get_command_buffer_passes(
+        &self,
+        passes: &[(&Arc<GraphicsPipeline>, &Arc<Framebuffer>, Arc<PersistentDescriptorSet>)],
+        vertex_buffer: &Subbuffer<[FVertex3d]>,
+        push_constant: crate::vk_present::fs::PushConstantData,
+    ) -> Arc<PrimaryAutoCommandBuffer> {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.mem_allocators.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::MultipleSubmit,
+        )
+        .unwrap();
+
+        for (pipeline, framebuffer, descriptor_set) in passes {
+            record_pass(&mut builder, pipeline, framebuffer, vertex_buffer, descriptor_set.clone(), push_constant);
+        }
+
+        builder.build().unwrap()
     }
 }
+
+/// Records a render pass drawing the fullscreen triangle with `pipeline` into `framebuffer`.
+pub fn record_pass(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pipeline: &Arc<GraphicsPipeline>,
+    framebuffer: &Arc<Framebuffer>,
+    vertex_buffer: &Subbuffer<[FVertex3d]>,
+    descriptor_set: Arc<PersistentDescriptorSet>,
+    push_constant: crate::vk_present::fs::PushConstantData,
+) {
+    builder
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values: vec![Some([0.0, 0.0, 1.0, 1.0].into())],
+                ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+            },
+            SubpassBeginInfo {
+                contents: SubpassContents::Inline,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .push_constants(pipeline.layout().clone(), 0, push_constant)
+        .unwrap()
+        .bind_pipeline_graphics(pipeline.clone())
+        .unwrap()
+        .bind_descriptor_sets(
+            PipelineBindPoint::Graphics,
+            pipeline.layout().clone(),
+            0,
+            descriptor_set,
+        )
+        .unwrap()
+        .bind_vertex_buffers(0, vertex_buffer.clone())
+        .unwrap()
+        .draw(vertex_buffer.len() as u32, 1, 0, 0)
+        .unwrap()
+        .end_render_pass(Default::default())
+        .unwrap();
+}
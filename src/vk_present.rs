@@ -8,23 +8,33 @@ use vulkano::swapchain::Surface;
 
 use vulkano::swapchain;
 use vulkano::{Validated, VulkanError};
-use vulkano::swapchain::{SwapchainPresentInfo, SwapchainAcquireFuture, PresentFuture};
+use vulkano::swapchain::{SwapchainPresentInfo, PresentFuture};
 
 use vulkano::sync::{self, GpuFuture};
 use vulkano::sync::future::FenceSignalFuture;
 
-use vulkano::buffer::Subbuffer;
+use vulkano::buffer::{BufferUsage, Subbuffer};
 use vulkano::command_buffer::{
-    CommandBufferExecFuture,
     PrimaryAutoCommandBuffer, 
     allocator::StandardCommandBufferAllocator
 };
 
+use vulkano::image::view::ImageView;
 use vulkano::pipeline::GraphicsPipeline;
-use vulkano::sync::future::JoinFuture;
 use vulkano::render_pass::Framebuffer;
 
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+
+use crate::deep_zoom::DEEP_ZOOM;
+use crate::fractal::{FractalKind, Precision, FRACTAL_KIND, FRACTAL_VIEW};
+use crate::texture::Texture;
 use crate::vk_pipeline::FVertex3d;
+use crate::vk_parallel::{strip, VkRecorder};
+use crate::vk_iterations::{iteration_key, IterationBuffer, IterationPipelines};
+use crate::vk_upload::{VkUploader, STAGING_RING_SIZE};
+use crate::vk_registry::{BufferHandle, ImageHandle, PipelineHandle, SamplerHandle, VkRegistry};
+use crate::vk_readback::image_to_rgba8;
+use crate::vk_screenshot::{save_screenshot, screenshot_path, SCREENSHOT_REQUESTED};
 use crate::vk_utils::Vk;
 
 pub mod vs {
@@ -37,10 +47,9 @@ pub mod vs {
             layout(location = 0) out vec3 pos;
 
             void main() {
-                vec2 outUV = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
-                gl_Position = vec4(outUV * 2.0 - 1.0, 0.0, 1.0);
+                gl_Position = vec4(position.xy, 0.0, 1.0);
 
-                pos = vec3(outUV * 2.0 - 1.0, 0.0);
+                pos = position;
             }
         ",
     }
@@ -49,54 +58,33 @@ pub mod vs {
 pub mod fs {
     vulkano_shaders::shader!{
         ty: "fragment",
-        src: "
-            #version 460
-
-            layout(location = 0) out vec4 f_color;
-            layout(location = 0) in vec3 pos;
-
-            layout(push_constant) uniform PushConstantData {
-                highp float time;
-                highp vec2 cpos;
-                highp vec2 ires;
-                highp float zoom;
-            } pc;
-
-            float mandelbrot(vec2 c) {
-                highp vec2 z = vec2(0.0, 0.0);
-                highp float i;
-
-                for (i = 0.0; i < 1.0; i += 0.01) {
-                    z = vec2(
-                        z.x * z.x - z.y * z.y + c.x,
-                        z.y * z.x + z.x * z.y + c.y
-                    );
-
-                    if (length(z) > 4.0) {
-                        break;
-                    }
-                }
-
-                return i;
-            }
-
-            void main() {
-                highp float i = 0.0;
-                float num_samples = 2;
+        custom_derives: [Clone, Copy, PartialEq, Debug],
+        path: "src/shaders/fractal.frag",
+    }
+}
 
-                // for (int s = 0; s < num_samples; ++s) {
-                    highp vec2 jitter = vec2(1, 1);
-                    highp vec2 samplePos = pos.xy * pc.zoom + jitter / pc.ires - pc.cpos;
-                    samplePos.y *= 1.0 / (pc.ires.x / pc.ires.y);
+// same shader with a wider iteration, drawn with `fs::PushConstantData` as its layout is identical
+pub mod fs_dd {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/shaders/fractal.frag",
+        define: [("DOUBLE_DOUBLE", "1")],
+    }
+}
 
-                    i += mandelbrot(samplePos);
-                // }
-                
-                highp float avgI = i;
+pub mod fs_f64 {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/shaders/fractal.frag",
+        define: [("FLOAT64", "1")],
+    }
+}
 
-                f_color = vec4(vec2(avgI), sin(pc.time), 1.0);
-            }
-        ",
+// second pass of two-pass rendering, see vk_iterations.rs
+pub mod colour_fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path: "src/shaders/colour.frag",
     }
 }
 
@@ -105,10 +93,32 @@ use once_cell::sync::Lazy;
 pub static FRAGMENT_PUSH_CONSTANTS: Lazy<Mutex<fs::PushConstantData>> = Lazy::new(|| {
     Mutex::new(
         fs::PushConstantData {
-            time: 0.0.into(),
             cpos: [0.0, 0.0],
             ires: [800.0, 800.0],
             zoom: 1.0,
+            max_iterations: 100,
+            escape_radius: 4.0,
+            palette_index: 0,
+            palette_offset: 0.0,
+            palette_scale: 1.0 / 32.0,
+            julia_c: [-0.8, 0.156],
+            julia: 0,
+            power: 3.0,
+            reference_len: 0,
+            scale_exp: 0,
+            cpos_lo: [0.0, 0.0],
+            zoom_lo: 0.0,
+            samples: 1,
+            sample_pattern: 0,
+            adaptive: 0,
+            trap_mode: 0,
+            trap_size: 0.5,
+            trap_pos: [0.0, 0.0],
+            distance_mode: 0,
+            distance_width: 1.0,
+            light_angle: std::f32::consts::FRAC_PI_4,
+            light_height: 1.5,
+            equalize: 0,
         }
     )
 });
@@ -119,63 +129,124 @@ pub static RECREATE_SWAPCHAIN: Lazy<Mutex<bool>> = Lazy::new(|| {Mutex::new(fals
 pub struct VkView {
     pub viewport: vulkano::pipeline::graphics::viewport::Viewport,
     pub shader_mods: Vec<Arc<vulkano::shader::ShaderModule>>,
-    pub vert_buffers: Vec<Subbuffer<[FVertex3d]>>,
+    pub colour_shader: Arc<vulkano::shader::ShaderModule>,
+    pub vert_buffer: Subbuffer<[FVertex3d]>,
+    pub strips: Vec<Subbuffer<[FVertex3d]>>,
     pub surface: Arc<Surface>,
     pub framebuffers : Vec<Arc<Framebuffer>>,
     pub render_pass: Arc<vulkano::render_pass::RenderPass>,
 
-    pub pipeline: Arc<GraphicsPipeline>,
+    // owned by `registry`, read through `pipeline()`, `palette()` and `reference_orbit()`
+    pub pipeline: PipelineHandle,
     pub layout: Arc<vulkano::pipeline::layout::PipelineLayout>,
-
-    pub command_buffers: Vec<Arc<PrimaryAutoCommandBuffer<StandardCommandBufferAllocator>>>,
+    pub palette_image: ImageHandle,
+    pub palette_view: Arc<ImageView>,
+    pub palette_sampler: SamplerHandle,
+    pub descriptor_set: Arc<PersistentDescriptorSet>,
+    // the fragment shader is specialized for this kind
+    pub kind: FractalKind,
+    // `shader_mods[1 + precision as usize]` is the fragment shader in use
+    pub precision: Precision,
+    pub reference_orbit: BufferHandle,
+    pub orbit_generation: u64,
+    // shared by every iteration buffer, rebuilt with `pipeline`
+    pub iteration_pipelines: IterationPipelines,
+    // one per swapchain image, so iterating never overwrites what a frame still in flight is
+    // coloured from; created on first use, dropped whenever the pipeline is rebuilt
+    pub iterations: Vec<Option<IterationBuffer>>,
+
+    // one slot per swapchain image, only re-recorded when the push constants it was recorded
+    // with are stale
+    pub command_buffers: Vec<Option<Arc<PrimaryAutoCommandBuffer<StandardCommandBufferAllocator>>>>,
+    pub recorded_push_constants: Vec<Option<fs::PushConstantData>>,
+    pub push_constants: fs::PushConstantData,
+
+    // work kept off the render thread, like encoding exports
+    pub pool: threadpool::ThreadPool,
+    // records the strips of frames drawn in one pass on `pool`
+    pub recorder: VkRecorder,
+    pub uploader: VkUploader,
+    pub registry: VkRegistry,
 }
 
 pub struct VkPresenter {
     pub frames_in_flight: usize,
     pub previous_fence_i: u32, 
-    pub fences: Vec<Option<Arc<FenceSignalFuture<PresentFuture<CommandBufferExecFuture<JoinFuture<Box<dyn GpuFuture>, SwapchainAcquireFuture>>>>>>>,
+    pub fences: Vec<Option<Arc<FenceSignalFuture<PresentFuture<Box<dyn GpuFuture>>>>>>,
+
+    // frame numbers of the submissions behind `fences`, used to tell the registry which frames
+    // have completed
+    pub frame: u64,
+    pub fence_frames: Vec<u64>,
+    pub completed_frame: u64,
 }
 
 use crate::vk_pipeline::vert;
 impl VkView {
-    pub fn new(vk: &mut Vk, window: Arc<winit::window::Window>) -> Self {
+    pub fn new(vk: &mut Vk, window: Arc<winit::window::Window>, pool: threadpool::ThreadPool) -> Self {
         let surface = Surface::from_window(vk.instance.clone(), window.clone()).unwrap();
         let viewport = vulkano::pipeline::graphics::viewport::Viewport {
             offset: [0.0, 0.0],
             extent: window.inner_size().into(),
             depth_range: 0.0..=1.0,
         };
-        let vert_buffers = vec![
-            vk.vertex_buffer(
-                vec![
-                    vert(0.0, 0.0, 0.0), 
-                    vert(0.0, 0.0, 0.0),
-                    vert(0.0, 0.0, 0.0),
-                ],
-            ),
-        ];
+        let mut uploader = VkUploader::new(vk, STAGING_RING_SIZE);
+        // one triangle covering the viewport
+        let vert_buffer = uploader.buffer(
+            vk,
+            vulkano::buffer::BufferUsage::VERTEX_BUFFER,
+            &[
+                vert(-1.0, -1.0, 0.0), 
+                vert(3.0, -1.0, 0.0),
+                vert(-1.0, 3.0, 0.0),
+            ],
+        );
+        // the same area in one strip per worker, for drawing in one pass
+        let strips = (0..pool.max_count() as u32)
+            .map(|i| uploader.buffer(vk, vulkano::buffer::BufferUsage::VERTEX_BUFFER, &strip(i, pool.max_count() as u32)))
+            .collect();
         let vs = vs::load(vk.device.clone()).unwrap();
         let fs = fs::load(vk.device.clone()).unwrap();
+        let mut shader_mods = vec![vs.clone(), fs.clone(), fs_dd::load(vk.device.clone()).unwrap()];
+        if vk.device.enabled_features().shader_float64 {
+            shader_mods.push(fs_f64::load(vk.device.clone()).unwrap());
+        }
 
         vk.set_swapchain(surface.clone(), &window);
         let images = vk.images.clone().unwrap();
+        let palette = Texture::palettes(vk, &mut uploader, vk.swapchain.clone().unwrap().image_format());
+        // placeholder until deep zoom computes an orbit
+        let reference_orbit = uploader.buffer(vk, BufferUsage::STORAGE_BUFFER, &[[0.0f32; 2]]);
+        if let Some(upload) = uploader.flush(vk) {
+            upload.wait(None).unwrap();
+        }
         let render_pass = vk.get_render_pass();
         let framebuffers = vk.get_framebuffers(&render_pass);
+        let kind = *FRACTAL_KIND.lock().unwrap();
         let (pipeline, layout) = vk.get_pipeline(
-            vs.clone(), 
-            fs.clone(), 
+            vs.entry_point("main").unwrap(), 
+            fs.specialize(kind.specialization()).unwrap().entry_point("main").unwrap(), 
             render_pass.clone(), 
             viewport.clone()
         );
 
-        let command_buffers = vk.get_command_buffers(
-            &pipeline, 
-            &framebuffers, 
-            &vert_buffers[0], 
-            layout.clone(), 
-            *FRAGMENT_PUSH_CONSTANTS.lock().unwrap()
+        let descriptor_set = descriptor_set(vk, &layout, &palette, &reference_orbit);
+        let colour_shader = colour_fs::load(vk.device.clone()).unwrap();
+        let iteration_pipelines = IterationPipelines::new(
+            vk,
+            &vs,
+            &fs,
+            &colour_shader,
+            kind,
+            &render_pass,
+            &viewport,
         );
 
+        let command_buffers = vec![None; framebuffers.len()];
+        let recorded_push_constants = vec![None; framebuffers.len()];
+        let iterations = (0..framebuffers.len()).map(|_| None).collect();
+        let mut registry = VkRegistry::new();
+
         *WINDOW_RESIZED.lock().unwrap() = false;
         *RECREATE_SWAPCHAIN .lock().unwrap( )= false;
 
@@ -183,12 +254,30 @@ impl VkView {
             surface,
             render_pass,
             viewport,
-            vert_buffers,
-            shader_mods: vec![vs, fs],
+            vert_buffer,
+            strips,
+            shader_mods,
+            colour_shader,
             framebuffers,
-            pipeline,
+            pipeline: registry.insert("fractal pipeline", pipeline),
             layout, 
+            palette_image: registry.insert("palette", palette.image),
+            palette_view: palette.view,
+            palette_sampler: registry.insert("palette sampler", palette.sampler),
+            descriptor_set,
+            kind,
+            precision: Precision::F32,
+            reference_orbit: registry.insert_buffer("reference orbit", reference_orbit),
+            orbit_generation: 0,
+            iteration_pipelines,
+            iterations,
             command_buffers,
+            recorded_push_constants,
+            push_constants: *FRAGMENT_PUSH_CONSTANTS.lock().unwrap(),
+            recorder: VkRecorder::new(vk, pool.clone()),
+            pool,
+            uploader,
+            registry,
         }
     }
 
@@ -208,34 +297,203 @@ impl VkView {
 
             vk.swapchain = Some(new_swpchain);
             vk.images = Some(new_imgs);
-            self.framebuffers = vk.get_framebuffers(&self.render_pass);
+            let old_framebuffers = std::mem::replace(
+                &mut self.framebuffers, 
+                vk.get_framebuffers(&self.render_pass)
+            );
+            self.registry.retire(old_framebuffers);
 
             if *WINDOW_RESIZED.lock().unwrap() {
                 *WINDOW_RESIZED.lock().unwrap() = false;
 
                 self.viewport.extent = new_dim.into();
-                (self.pipeline, self.layout) = vk.get_pipeline(
-                    self.shader_mods[0].clone(), 
-                    self.shader_mods[1].clone(), 
-                    self.render_pass.clone(), 
-                    self.viewport.clone()
-                );
+                self.rebuild_pipeline(vk);
+            }
+
+            self.invalidate_command_buffers();
+        }
+    }
+
+    /// Recreates the pipeline for the current viewport, fractal kind and precision, retiring
+    /// the old one.
+    pub fn rebuild_pipeline(&mut self, vk: &Vk) {
+        let (pipeline, layout) = vk.get_pipeline(
+            self.shader_mods[0].entry_point("main").unwrap(), 
+            self.shader_mods[1 + self.precision as usize].specialize(self.kind.specialization()).unwrap().entry_point("main").unwrap(), 
+            self.render_pass.clone(), 
+            self.viewport.clone()
+        );
+        self.registry.destroy(self.pipeline);
+        self.pipeline = self.registry.insert("fractal pipeline", pipeline);
+        self.descriptor_set = descriptor_set(vk, &layout, &self.palette(), &self.reference_orbit());
+        self.layout = layout;
+        let iteration_pipelines = IterationPipelines::new(
+            vk,
+            &self.shader_mods[0],
+            &self.shader_mods[1 + self.precision as usize],
+            &self.colour_shader,
+            self.kind,
+            &self.render_pass,
+            &self.viewport,
+        );
+        let old = std::mem::replace(&mut self.iteration_pipelines, iteration_pipelines);
+        self.registry.retire(old);
+        for iterations in self.iterations.iter_mut() {
+            if let Some(iterations) = iterations.take() {
+                self.registry.retire(iterations);
             }
         }
+
+        self.invalidate_command_buffers();
+    }
+
+    pub fn invalidate_command_buffers(&mut self) {
+        self.command_buffers = vec![None; self.framebuffers.len()];
+        self.recorded_push_constants = vec![None; self.framebuffers.len()];
+        self.iterations.resize_with(self.framebuffers.len(), || None);
     }
 
     pub fn update(&mut self, vk: &mut Vk) {
-        self.command_buffers = vk.get_command_buffers(
-            &self.pipeline.clone(), 
-            &self.framebuffers, 
-            &self.vert_buffers[0], 
-            self.layout.clone(),
-            *FRAGMENT_PUSH_CONSTANTS.lock().unwrap(),
+        self.push_constants = *FRAGMENT_PUSH_CONSTANTS.lock().unwrap();
+
+        let kind = *FRACTAL_KIND.lock().unwrap();
+        let precision = Precision::for_zoom(
+            FRACTAL_VIEW.lock().unwrap().zoom,
+            vk.device.enabled_features().shader_float64,
         );
+        if kind != self.kind || precision != self.precision {
+            if precision != self.precision {
+                println!("fractal precision {:?}", precision);
+            }
+            self.kind = kind;
+            self.precision = precision;
+            self.rebuild_pipeline(vk);
+        }
+
+        if let Some(deep) = DEEP_ZOOM.lock().unwrap().as_ref() {
+            if deep.generation != self.orbit_generation {
+                let orbit = self.uploader.buffer(vk, BufferUsage::STORAGE_BUFFER, &deep.orbit);
+                if let Some(upload) = self.uploader.flush(vk) {
+                    upload.wait(None).unwrap();
+                }
+
+                self.registry.destroy(self.reference_orbit);
+                self.reference_orbit = self.registry.insert_buffer("reference orbit", orbit);
+                self.orbit_generation = deep.generation;
+                self.descriptor_set = descriptor_set(vk, &self.layout, &self.palette(), &self.reference_orbit());
+                let mut all = std::mem::take(&mut self.iterations);
+                for iterations in all.iter_mut().flatten() {
+                    iterations.set_reference_orbit(vk, self);
+                }
+                self.iterations = all;
+                self.invalidate_command_buffers();
+            }
+        } else {
+            // the next deep zoom starts counting orbits from scratch
+            self.orbit_generation = 0;
+        }
+    }
+
+    pub fn pipeline(&self) -> &Arc<GraphicsPipeline> {
+        self.registry.get(self.pipeline).unwrap()
+    }
+
+    pub fn palette(&self) -> Texture {
+        Texture {
+            image: self.registry.get(self.palette_image).unwrap().clone(),
+            view: self.palette_view.clone(),
+            sampler: self.registry.get(self.palette_sampler).unwrap().clone(),
+        }
+    }
+
+    pub fn reference_orbit(&self) -> Subbuffer<[[f32; 2]]> {
+        self.registry.buffer(self.reference_orbit).unwrap()
+    }
+
+    /// Whether the frame is drawn in two passes, see `IterationBuffer`.
+    pub fn two_pass(&self) -> bool {
+        self.push_constants.samples <= 1
+    }
+
+    /// Whether the next frame drawn to `image_i` has to run the iteration pass of two-pass
+    /// rendering.
+    pub fn iterations_stale(&self, image_i: usize) -> bool {
+        self.two_pass()
+            && self.iterations[image_i].as_ref().and_then(|iterations| iterations.rendered)
+                != Some(iteration_key(&self.push_constants))
+    }
+
+    /// Returns the command buffer for the acquired image, recording it only if it was never
+    /// recorded or was recorded with different push constants. Drawn in one pass, every strip
+    /// is recorded as a secondary command buffer on the recorder's pool.
+    ///
+    /// Drawn in two passes, a frame that has to iterate gets a command buffer of its own that
+    /// is not kept, submitted again it would iterate again.
+    pub fn command_buffer(&mut self, vk: &Vk, image_i: usize) 
+    -> Arc<PrimaryAutoCommandBuffer<StandardCommandBufferAllocator>> {
+        if self.two_pass() {
+            let format = IterationBuffer::format_for(&self.push_constants);
+            if self.iterations[image_i].as_ref().map(|iterations| iterations.format) != Some(format) {
+                let iterations = IterationBuffer::new(vk, self, format);
+                if let Some(old) = self.iterations[image_i].replace(iterations) {
+                    self.registry.retire(old);
+                }
+            }
+
+            // the colouring pass below reads the equalization, which is counted right here
+            let stale = self.iterations_stale(image_i);
+            let key = Some(iteration_key(&self.push_constants));
+            if self.push_constants.equalize != 0 && (stale || self.iterations[image_i].as_ref().unwrap().equalized != key) {
+                let mut iterations = self.iterations[image_i].take().unwrap();
+                iterations.equalize(vk, self, stale);
+                self.iterations[image_i] = Some(iterations);
+            } else if stale {
+                let iterations = self.iterations[image_i].as_ref().unwrap();
+                let command_buffer = iterations.command_buffer(vk, self, &self.framebuffers[image_i], true);
+                self.iterations[image_i].as_mut().unwrap().rendered = Some(iteration_key(&self.push_constants));
+                return command_buffer;
+            }
+        }
+
+        if self.recorded_push_constants[image_i] != Some(self.push_constants) {
+            self.command_buffers[image_i] = Some(if self.two_pass() {
+                let iterations = self.iterations[image_i].as_ref().unwrap();
+                iterations.command_buffer(vk, self, &self.framebuffers[image_i], false)
+            } else {
+                vk.get_command_buffer_parallel(
+                    &self.recorder,
+                    self.pipeline(), 
+                    &self.framebuffers[image_i], 
+                    &self.strips, 
+                    self.descriptor_set.clone(),
+                    self.push_constants,
+                )
+            });
+            self.recorded_push_constants[image_i] = Some(self.push_constants);
+        }
 
+        self.command_buffers[image_i].clone().unwrap()
     }
 }
 
+pub fn descriptor_set(
+    vk: &Vk,
+    layout: &Arc<vulkano::pipeline::layout::PipelineLayout>,
+    palette: &Texture,
+    reference_orbit: &Subbuffer<[[f32; 2]]>,
+) -> Arc<PersistentDescriptorSet> {
+    PersistentDescriptorSet::new(
+        &vk.mem_allocators.descriptor_set_allocator,
+        layout.set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::image_view_sampler(0, palette.view.clone(), palette.sampler.clone()),
+            WriteDescriptorSet::buffer(1, reference_orbit.clone()),
+        ],
+        [],
+    )
+    .unwrap()
+}
+
 impl VkPresenter {
     pub fn new(vk: &mut Vk) -> Self {
         let images = vk.images.clone().unwrap();
@@ -246,11 +504,14 @@ impl VkPresenter {
         Self {
             frames_in_flight,
             fences,
-            previous_fence_i
+            previous_fence_i,
+            frame: 0,
+            fence_frames: vec![0; frames_in_flight],
+            completed_frame: 0,
         }
     }
 
-    pub fn present(&mut self, vk: &mut Vk, view: &VkView) {
+    pub fn present(&mut self, vk: &mut Vk, view: &mut VkView) {
         let (image_i, suboptimal, acquire_future) =
             match swapchain::acquire_next_image(vk.swapchain.clone().unwrap(), None)
                 .map_err(Validated::unwrap)
@@ -271,6 +532,15 @@ impl VkPresenter {
             image_fence.wait(None).unwrap();
         }
 
+        for (fence, frame) in self.fences.iter().zip(&self.fence_frames) {
+            if let Some(fence) = fence {
+                if fence.is_signaled().unwrap_or(false) {
+                    self.completed_frame = self.completed_frame.max(*frame);
+                }
+            }
+        }
+        view.registry.collect(self.completed_frame);
+
         let previous_future = match self.fences[self.previous_fence_i as usize].clone() {
             None => {
                 let mut now = sync::now(vk.device.clone());
@@ -280,16 +550,45 @@ impl VkPresenter {
             Some(fence) => fence.boxed(),
         };
 
+        let command_buffer = view.command_buffer(vk, image_i as usize);
+
         let future = previous_future
             .join(acquire_future)
-            .then_execute(vk.queue.clone(), view.command_buffers[image_i as usize].clone())
-            .unwrap()
+            .then_execute(vk.queue.clone(), command_buffer)
+            .unwrap();
+
+        // copy the rendered image out before it is handed back to the presentation engine
+        let screenshot_image = vk.images.clone().unwrap()[image_i as usize].clone();
+        let screenshot = if std::mem::take(&mut *SCREENSHOT_REQUESTED.lock().unwrap()) {
+            if screenshot_image.usage().intersects(vulkano::image::ImageUsage::TRANSFER_SRC) {
+                Some(vk.image_readback(screenshot_image.clone()))
+            } else {
+                println!("swapchain images can not be transfer sources; screenshot skipped");
+                None
+            }
+        } else {
+            None
+        };
+
+        let future = match &screenshot {
+            Some((copy_buffer, _)) => future
+                .then_execute(vk.queue.clone(), copy_buffer.clone())
+                .unwrap()
+                .boxed(),
+            None => future.boxed(),
+        };
+
+        let future = future
             .then_swapchain_present(
                 vk.queue.clone(),
                 SwapchainPresentInfo::swapchain_image_index(vk.swapchain.clone().unwrap(), image_i),
             )
             .then_signal_fence_and_flush();
 
+        self.frame += 1;
+        self.fence_frames[image_i as usize] = self.frame;
+        view.registry.frame = self.frame;
+
         self.fences[image_i as usize] = match future.map_err(Validated::unwrap) {
             Ok(value) => Some(Arc::new(value)),
             Err(VulkanError::OutOfDate) => {
@@ -302,5 +601,24 @@ impl VkPresenter {
             }
         };
         self.previous_fence_i = image_i;
+
+        if let (Some((_, dst)), Some(fence)) = (screenshot, &self.fences[image_i as usize]) {
+            fence.wait(None).unwrap();
+            self.save_screenshot(&screenshot_image, dst, view);
+        }
+    }
+
+    /// Captures the next presented frame and writes it to the working directory, named after
+    /// the current view.
+    pub fn request_screenshot(&self) {
+        *SCREENSHOT_REQUESTED.lock().unwrap() = true;
+    }
+
+    fn save_screenshot(&self, image: &vulkano::image::Image, dst: Subbuffer<[u8]>, view: &VkView) {
+        let img = image_to_rgba8(image, &dst.read().unwrap());
+        let path = screenshot_path(&FRACTAL_VIEW.lock().unwrap());
+
+        // encoding is slow, keep it off the render thread
+        view.pool.execute(move || save_screenshot(img, &path));
     }
 }
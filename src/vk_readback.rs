@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, CopyImageToBufferInfo,
+    PrimaryAutoCommandBuffer,
+};
+use vulkano::format::Format;
+use vulkano::image::Image;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+
+use crate::vk_memory::{track_buffer, MemoryCategory};
+use crate::vk_utils::Vk;
+
+impl Vk {
+    /// Host readable buffer that transfers can be copied into.
+    pub fn readback_buffer<T>(&self, len: u64) -> Subbuffer<[T]>
+    where
+        T: BufferContents
+    {
+        let buffer = Buffer::new_slice::<T>(
+            self.mem_allocators.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            len,
+        )
+        .expect("failed to create readback buffer");
+
+        track_buffer(MemoryCategory::Readback, buffer.buffer());
+        buffer
+    }
+
+    /// Copies `buffer` (which needs `TRANSFER_SRC` usage) back to the host, blocking until the
+    /// copy has finished.
+    pub fn read_buffer<T>(&self, buffer: Subbuffer<[T]>) -> Vec<T>
+    where
+        T: BufferContents + Copy
+    {
+        let dst = self.readback_buffer::<T>(buffer.len());
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.mem_allocators.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        builder
+            .copy_buffer(CopyBufferInfo::buffers(buffer, dst.clone()))
+            .unwrap();
+
+        self.sync(builder.build().unwrap());
+
+        let data = dst.read().unwrap().to_vec();
+        data
+    }
+
+    /// Copies mip level 0 / layer 0 of `image` (which needs `TRANSFER_SRC` usage) back to the
+    /// host and converts it to 8 bit RGBA, blocking until the copy has finished.
+    pub fn read_image(&self, image: Arc<Image>) -> image::RgbaImage {
+        let (command_buffer, dst) = self.image_readback(image.clone());
+        self.sync(command_buffer);
+
+        let data = dst.read().unwrap();
+        image_to_rgba8(&image, &data)
+    }
+
+    /// Records a copy of mip level 0 / layer 0 of `image` into a new host readable buffer
+    /// without submitting it, for callers that chain the copy onto their own futures.
+    pub fn image_readback(&self, image: Arc<Image>) -> (Arc<PrimaryAutoCommandBuffer>, Subbuffer<[u8]>) {
+        let [width, height, _] = image.extent();
+        let row_pitch = width as u64 * image.format().block_size();
+        let dst = self.readback_buffer::<u8>(row_pitch * height as u64);
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.mem_allocators.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        let mut copy_info = CopyImageToBufferInfo::image_buffer(image, dst.clone());
+        copy_info.regions[0].image_subresource.array_layers = 0..1;
+        copy_info.regions[0].image_extent = [width, height, 1];
+        builder
+            .copy_image_to_buffer(copy_info)
+            .unwrap();
+
+        (builder.build().unwrap(), dst)
+    }
+}
+
+/// Converts the contents of a buffer filled by `Vk::image_readback` into an 8 bit RGBA image.
+pub fn image_to_rgba8(image: &Image, data: &[u8]) -> image::RgbaImage {
+    let [width, height, _] = image.extent();
+    let row_pitch = width as usize * image.format().block_size() as usize;
+    to_rgba8(image.format(), data, width, height, row_pitch)
+}
+
+/// Converts tightly packed texels with `row_pitch` bytes per row into an 8 bit RGBA image.
+/// sRGB formats are copied as is since `RgbaImage` is sRGB encoded already, float formats are
+/// clamped to `0..1`.
+pub fn to_rgba8(format: Format, data: &[u8], width: u32, height: u32, row_pitch: usize) -> image::RgbaImage {
+    let texel_size = format.block_size() as usize;
+
+    let texel: fn(&[u8]) -> [u8; 4] = match format {
+        Format::R8G8B8A8_UNORM | Format::R8G8B8A8_SRGB => |t| [t[0], t[1], t[2], t[3]],
+        Format::B8G8R8A8_UNORM | Format::B8G8R8A8_SRGB => |t| [t[2], t[1], t[0], t[3]],
+        Format::R8_UNORM | Format::R8_SRGB => |t| [t[0], t[0], t[0], 255],
+        Format::R16G16B16A16_SFLOAT => |t| {
+            let c = |i: usize| unorm8(f16_to_f32(u16::from_le_bytes([t[i * 2], t[i * 2 + 1]])));
+            [c(0), c(1), c(2), c(3)]
+        },
+        Format::R32G32B32A32_SFLOAT => |t| {
+            let c = |i: usize| unorm8(f32::from_le_bytes(t[i * 4..i * 4 + 4].try_into().unwrap()));
+            [c(0), c(1), c(2), c(3)]
+        },
+        Format::R32_SFLOAT => |t| {
+            let c = unorm8(f32::from_le_bytes(t[0..4].try_into().unwrap()));
+            [c, c, c, 255]
+        },
+        _ => panic!("read_image: unsupported format {format:?}"),
+    };
+
+    image::RgbaImage::from_fn(width, height, |x, y| {
+        let offset = y as usize * row_pitch + x as usize * texel_size;
+        image::Rgba(texel(&data[offset..offset + texel_size]))
+    })
+}
+
+fn unorm8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    match exp {
+        0 => sign * mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => sign * f32::INFINITY,
+        31 => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
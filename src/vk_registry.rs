@@ -0,0 +1,271 @@
+use std::any::Any;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferContents, Subbuffer};
+use vulkano::image::sampler::Sampler;
+use vulkano::image::Image;
+use vulkano::pipeline::GraphicsPipeline;
+
+/// Typed index into a `Pool`. A handle whose resource was destroyed stays invalid even after
+/// its slot is reused, since the slot's generation no longer matches.
+pub struct Handle<T> {
+    pub index: u32,
+    pub generation: u32,
+    _ty: PhantomData<fn() -> T>,
+}
+
+pub type BufferHandle = Handle<Subbuffer<[u8]>>;
+pub type ImageHandle = Handle<Arc<Image>>;
+pub type SamplerHandle = Handle<Arc<Sampler>>;
+pub type PipelineHandle = Handle<Arc<GraphicsPipeline>>;
+
+// manual impls so that `T` does not need to implement any of these
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+    name: String,
+}
+
+pub struct Pool<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
+        Self {
+            slots: vec![],
+            free: vec![],
+        }
+    }
+}
+
+impl<T> Pool<T> {
+    pub fn insert(&mut self, name: &str, value: T) -> Handle<T> {
+        let index = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                slot.name = name.to_owned();
+                index
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                    name: name.to_owned(),
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+
+        Handle {
+            index,
+            generation: self.slots[index as usize].generation,
+            _ty: PhantomData,
+        }
+    }
+
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        let slot = self.slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)?;
+        let value = slot.value.take()?;
+
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.name.clear();
+        self.free.push(handle.index);
+
+        Some(value)
+    }
+
+    /// First live resource registered under `name`.
+    pub fn find(&self, name: &str) -> Option<Handle<T>> {
+        self.slots
+            .iter()
+            .position(|slot| slot.value.is_some() && slot.name == name)
+            .map(|index| Handle {
+                index: index as u32,
+                generation: self.slots[index].generation,
+                _ty: PhantomData,
+            })
+    }
+
+    pub fn name(&self, handle: Handle<T>) -> Option<&str> {
+        self.get(handle)?;
+        Some(&self.slots[handle.index as usize].name)
+    }
+}
+
+/// Types that live in one of the registry's pools.
+pub trait Registered: Sized + Send + Sync + 'static {
+    fn pool(registry: &VkRegistry) -> &Pool<Self>;
+    fn pool_mut(registry: &mut VkRegistry) -> &mut Pool<Self>;
+}
+
+macro_rules! registered {
+    ($ty:ty, $field:ident) => {
+        impl Registered for $ty {
+            fn pool(registry: &VkRegistry) -> &Pool<Self> {
+                &registry.$field
+            }
+
+            fn pool_mut(registry: &mut VkRegistry) -> &mut Pool<Self> {
+                &mut registry.$field
+            }
+        }
+    };
+}
+
+registered!(Subbuffer<[u8]>, buffers);
+registered!(Arc<Image>, images);
+registered!(Arc<Sampler>, samplers);
+registered!(Arc<GraphicsPipeline>, pipelines);
+
+/// Owns GPU resources behind handles. Destroyed resources are kept alive until every frame
+/// submitted before their destruction has completed.
+#[derive(Default)]
+pub struct VkRegistry {
+    pub buffers: Pool<Subbuffer<[u8]>>,
+    pub images: Pool<Arc<Image>>,
+    pub samplers: Pool<Arc<Sampler>>,
+    pub pipelines: Pool<Arc<GraphicsPipeline>>,
+
+    /// Last frame submitted, set by the presenter.
+    pub frame: u64,
+    retired: Vec<(u64, Box<dyn Any + Send + Sync>)>,
+}
+
+impl VkRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `value` under `name`, which no other live resource of the same type may use.
+    pub fn insert<T: Registered>(&mut self, name: &str, value: T) -> Handle<T> {
+        debug_assert!(self.find::<T>(name).is_none(), "{name} is already registered");
+        T::pool_mut(self).insert(name, value)
+    }
+
+    pub fn get<T: Registered>(&self, handle: Handle<T>) -> Option<&T> {
+        T::pool(self).get(handle)
+    }
+
+    pub fn find<T: Registered>(&self, name: &str) -> Option<Handle<T>> {
+        T::pool(self).find(name)
+    }
+
+    pub fn name<T: Registered>(&self, handle: Handle<T>) -> Option<&str> {
+        T::pool(self).name(handle)
+    }
+
+    /// Registers a typed buffer; read it back with `buffer`.
+    pub fn insert_buffer<T: BufferContents>(&mut self, name: &str, buffer: Subbuffer<[T]>) -> BufferHandle {
+        self.insert(name, buffer.into_bytes())
+    }
+
+    pub fn buffer<T: BufferContents>(&self, handle: BufferHandle) -> Option<Subbuffer<[T]>> {
+        self.get(handle).map(|buffer| buffer.clone().reinterpret::<[T]>())
+    }
+
+    /// Invalidates `handle` right away, but only drops the resource once the frames that could
+    /// still be using it have completed. Returns `false` for stale handles.
+    pub fn destroy<T: Registered>(&mut self, handle: Handle<T>) -> bool {
+        if let Some(name) = self.name(handle) {
+            println!("registry: retiring {name} {handle:?} at frame {}", self.frame);
+        }
+        match T::pool_mut(self).remove(handle) {
+            Some(value) => {
+                self.retire(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Keeps any resource alive until the current frame has completed.
+    pub fn retire<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.retired.push((self.frame, Box::new(value)));
+    }
+
+    /// Drops every resource retired at or before `completed_frame`.
+    pub fn collect(&mut self, completed_frame: u64) {
+        self.retired.retain(|(frame, _)| *frame > completed_frame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reused_slots_invalidate_old_handles() {
+        let mut pool = Pool::default();
+        let first = pool.insert("first", 1);
+        assert_eq!(pool.remove(first), Some(1));
+        assert_eq!(pool.remove(first), None);
+
+        let second = pool.insert("second", 2);
+        assert_eq!(second.index, first.index);
+        assert_eq!(pool.get(first), None);
+        assert_eq!(pool.get(second), Some(&2));
+    }
+
+    #[test]
+    fn names_follow_the_live_resource() {
+        let mut pool = Pool::default();
+        let a = pool.insert("palette", 1);
+        let b = pool.insert("orbit", 2);
+        assert_eq!(pool.find("orbit"), Some(b));
+        assert_eq!(pool.name(a), Some("palette"));
+
+        pool.remove(a);
+        assert_eq!(pool.find("palette"), None);
+        assert_eq!(pool.name(a), None);
+
+        pool.remove(b);
+        let c = pool.insert("orbit", 3);
+        assert_ne!(c, b);
+        assert_eq!(pool.find("orbit"), Some(c));
+        assert_eq!(pool.name(c), Some("orbit"));
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::fractal::FractalView;
+
+/// Set to request a capture of the next presented frame.
+pub static SCREENSHOT_REQUESTED: Lazy<Mutex<bool>> = Lazy::new(|| {Mutex::new(false)} );
+
+/// Extension of the screenshots written by `save_screenshot`, either "png" or "jpg".
+pub static SCREENSHOT_FORMAT: Lazy<Mutex<&'static str>> = Lazy::new(|| {Mutex::new("png")} );
+
+/// File name of a screenshot of `view`, e.g.
+/// `screenshot_-0.743640_0.131820_1.5e-4.png`.
+pub fn screenshot_path(view: &FractalView) -> PathBuf {
+    PathBuf::from(format!(
+        "screenshot_{:.6}_{:.6}_{:.1e}.{}",
+        view.cpos[0],
+        view.cpos[1],
+        view.zoom,
+        *SCREENSHOT_FORMAT.lock().unwrap(),
+    ))
+}
+
+/// Writes `img` to `path`, picking the encoder from the extension. The swapchain alpha is
+/// meaningless once composited, so the image is written opaque.
+pub fn save_screenshot(mut img: image::RgbaImage, path: &Path) {
+    for pixel in img.pixels_mut() {
+        pixel[3] = 255;
+    }
+
+    let result = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("jpg") | Some("jpeg") => image::DynamicImage::ImageRgba8(img).to_rgb8().save(path),
+        _ => img.save(path),
+    };
+
+    match result {
+        Ok(()) => println!("saved screenshot to {}", path.display()),
+        Err(e) => println!("failed to save screenshot to {}: {e}", path.display()),
+    }
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{
+    AutoCommandBufferBuilder, CommandBufferExecFuture, CommandBufferUsage, CopyBufferInfo,
+    CopyBufferToImageInfo, PrimaryAutoCommandBuffer,
+};
+use vulkano::image::Image;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+use vulkano::sync::future::{FenceSignalFuture, NowFuture};
+use vulkano::sync::{self, GpuFuture};
+
+use crate::vk_memory::{track_buffer, MemoryCategory};
+use crate::vk_utils::Vk;
+
+pub type UploadFuture = Arc<FenceSignalFuture<CommandBufferExecFuture<NowFuture>>>;
+
+/// Default size of the host visible staging ring.
+pub const STAGING_RING_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Uploads data into device local buffers and images through a host visible staging ring.
+/// Copies are batched into a single command buffer until `flush` submits them.
+pub struct VkUploader {
+    pub staging: Subbuffer<[u8]>,
+    pub alignment: u64,
+    pub head: u64,
+
+    pub builder: Option<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>>,
+    pub pending: Vec<UploadFuture>,
+}
+
+impl VkUploader {
+    pub fn new(vk: &Vk, size: u64) -> Self {
+        let staging = Buffer::new_slice::<u8>(
+            vk.mem_allocators.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            size,
+        )
+        .expect("failed to create staging buffer");
+        track_buffer(MemoryCategory::Staging, staging.buffer());
+
+        // 16 covers the texel size of every format we upload
+        let alignment = vk.physical_device
+            .properties()
+            .optimal_buffer_copy_offset_alignment
+            .as_devicesize()
+            .max(16);
+
+        Self {
+            staging,
+            alignment,
+            head: 0,
+            builder: None,
+            pending: vec![],
+        }
+    }
+
+    /// Creates a device local buffer with `usage` and schedules `data` to be copied into it.
+    /// Vulkan has no empty buffers, for empty `data` it is one uninitialized element long.
+    pub fn buffer<T>(&mut self, vk: &Vk, usage: BufferUsage, data: &[T]) -> Subbuffer<[T]>
+    where
+        T: BufferContents + Copy
+    {
+        let buffer = Buffer::new_slice::<T>(
+            vk.mem_allocators.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: usage | BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE,
+                ..Default::default()
+            },
+            data.len().max(1) as u64,
+        )
+        .expect("failed to create buffer");
+
+        let category = if usage.intersects(BufferUsage::VERTEX_BUFFER | BufferUsage::INDEX_BUFFER) {
+            MemoryCategory::Vertex
+        } else if usage.intersects(BufferUsage::UNIFORM_BUFFER) {
+            MemoryCategory::Uniform
+        } else {
+            MemoryCategory::Other
+        };
+        track_buffer(category, buffer.buffer());
+
+        if let Some(src) = self.stage(vk, data) {
+            self.builder(vk)
+                .copy_buffer(CopyBufferInfo::buffers(src, buffer.clone()))
+                .unwrap();
+        }
+
+        buffer
+    }
+
+    /// Schedules `data` to be copied into mip level 0 of every layer of `image`, which must have
+    /// been created with `TRANSFER_DST` usage and be tightly packed in `data`.
+    pub fn image(&mut self, vk: &Vk, image: Arc<Image>, data: &[u8]) {
+        if let Some(src) = self.stage(vk, data) {
+            self.builder(vk)
+                .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(src, image))
+                .unwrap();
+        }
+    }
+
+    /// Submits every copy recorded since the last flush. Returns `None` if nothing was recorded.
+    pub fn flush(&mut self, vk: &Vk) -> Option<UploadFuture> {
+        let builder = self.builder.take()?;
+
+        let future = Arc::new(
+            sync::now(vk.device.clone())
+                .then_execute(vk.queue.clone(), builder.build().unwrap())
+                .unwrap()
+                .then_signal_fence_and_flush()
+                .unwrap(),
+        );
+
+        self.pending.retain(|future| !future.is_signaled().unwrap_or(false));
+        self.pending.push(future.clone());
+
+        Some(future)
+    }
+
+    pub fn builder(&mut self, vk: &Vk) -> &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        self.builder.get_or_insert_with(|| {
+            AutoCommandBufferBuilder::primary(
+                &vk.mem_allocators.command_buffer_allocator,
+                vk.queue.queue_family_index(),
+                CommandBufferUsage::OneTimeSubmit,
+            )
+            .unwrap()
+        })
+    }
+
+    /// Copies `data` into the staging ring and returns the range it occupies, `None` for empty
+    /// `data` as subbuffers can not be empty. When the ring is full the pending batch is flushed
+    /// and every in-flight upload is waited on before wrapping.
+    fn stage<T>(&mut self, vk: &Vk, data: &[T]) -> Option<Subbuffer<[T]>>
+    where
+        T: BufferContents + Copy
+    {
+        let len = std::mem::size_of_val(data) as u64;
+        if len == 0 {
+            return None;
+        }
+
+        if len > self.staging.len() {
+            // does not fit in the ring at all; use a dedicated staging buffer
+            let buffer = Buffer::from_iter(
+                vk.mem_allocators.memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::TRANSFER_SRC,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                        | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+                data.iter().copied(),
+            )
+            .expect("failed to create staging buffer");
+
+            track_buffer(MemoryCategory::Staging, buffer.buffer());
+            return Some(buffer);
+        }
+
+        if self.head + len > self.staging.len() {
+            self.flush(vk);
+            for future in self.pending.drain(..) {
+                future.wait(None).unwrap();
+            }
+            self.head = 0;
+        }
+
+        let src = self.staging.clone()
+            .slice(self.head..self.head + len)
+            .reinterpret::<[T]>();
+        src.write().unwrap().copy_from_slice(data);
+
+        self.head = (self.head + len).next_multiple_of(self.alignment);
+
+        Some(src)
+    }
+}
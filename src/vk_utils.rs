@@ -90,8 +90,15 @@ impl Vk {
                 }],
                 enabled_extensions: vulkano::device::DeviceExtensions {
                     khr_swapchain: true,
+                    ext_memory_budget: physical_device.supported_extensions().ext_memory_budget,
                     ..vulkano::device::DeviceExtensions::empty()
                 },
+                enabled_features: vulkano::device::Features {
+                    sampler_anisotropy: physical_device.supported_features().sampler_anisotropy,
+                    // picks the f64 fractal shader over the double-double one when present
+                    shader_float64: physical_device.supported_features().shader_float64,
+                    ..vulkano::device::Features::empty()
+                },
                 ..Default::default()
             },
         )
@@ -151,7 +158,9 @@ impl Vk {
                 min_image_count: caps.min_image_count + 1,
                 image_format,
                 image_extent: dimensions.into(),
-                image_usage: ImageUsage::COLOR_ATTACHMENT, 
+                // transfer source so frames can be read back for screenshots
+                image_usage: ImageUsage::COLOR_ATTACHMENT
+                    | (caps.supported_usage_flags & ImageUsage::TRANSFER_SRC), 
                 present_mode: vulkano::swapchain::PresentMode::Mailbox,
                 composite_alpha,
                 ..Default::default()
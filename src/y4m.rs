@@ -0,0 +1,156 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Streams frames into a YUV4MPEG2 file, the uncompressed format ffmpeg, x264 and friends read
+/// directly. Frames are converted to 8 bit 4:2:0 with the BT.709 matrix in limited range, the
+/// usual choice for HD video.
+pub struct Y4mWriter {
+    file: BufWriter<File>,
+    size: [u32; 2],
+    // one frame's planes, reused
+    planes: Vec<u8>,
+}
+
+impl Y4mWriter {
+    pub fn create(path: &Path, size: [u32; 2], fps: f64) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let (num, den) = frame_rate(fps);
+        writeln!(
+            file,
+            "YUV4MPEG2 W{} H{} F{num}:{den} Ip A1:1 C420jpeg XCOLORRANGE=LIMITED",
+            size[0], size[1],
+        )?;
+
+        Ok(Self {
+            file,
+            size,
+            planes: vec![],
+        })
+    }
+
+    /// Appends `img`, which has to be the size given to `create`.
+    pub fn write_frame(&mut self, img: &image::RgbaImage) -> io::Result<()> {
+        assert_eq!([img.width(), img.height()], self.size, "y4m frame size changed");
+        to_yuv420(img, &mut self.planes);
+
+        self.file.write_all(b"FRAME\n")?;
+        self.file.write_all(&self.planes)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// `fps` as a reduced fraction: NTSC style rates like 29.97 as thousands over 1001, anything
+/// else to the nearest thousandth.
+fn frame_rate(fps: f64) -> (u64, u64) {
+    let ntsc = fps * 1.001;
+    let (num, den) = if (ntsc - ntsc.round()).abs() < 1e-3 && fps.fract() != 0.0 {
+        (ntsc.round() as u64 * 1000, 1001)
+    } else {
+        ((fps * 1000.0).round() as u64, 1000)
+    };
+
+    let (mut a, mut b) = (num, den);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    (num / a, den / a)
+}
+
+/// Planes of `img` in the layout of a y4m frame: full size luma, then Cb and Cr at half size,
+/// rounded up.
+fn to_yuv420(img: &image::RgbaImage, planes: &mut Vec<u8>) {
+    let [w, h] = [img.width() as usize, img.height() as usize];
+    let [cw, ch] = [w.div_ceil(2), h.div_ceil(2)];
+
+    planes.clear();
+    planes.resize(w * h + 2 * cw * ch, 0);
+    let (luma, chroma) = planes.split_at_mut(w * h);
+    let (cb, cr) = chroma.split_at_mut(cw * ch);
+
+    let rgb = |x: usize, y: usize| {
+        let p = img.get_pixel(x as u32, y as u32);
+        [p[0], p[1], p[2]].map(|c| c as f32 / 255.0)
+    };
+
+    for y in 0..h {
+        for x in 0..w {
+            let [r, g, b] = rgb(x, y);
+            luma[y * w + x] = (16.0 + 219.0 * (0.2126 * r + 0.7152 * g + 0.0722 * b)).round() as u8;
+        }
+    }
+
+    // chroma of the average of each 2x2 block, of what is left of it at odd edges
+    for cy in 0..ch {
+        for cx in 0..cw {
+            let mut sum = [0.0; 3];
+            let mut n = 0.0;
+            for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| (cx * 2 + dx, cy * 2 + dy)) {
+                if x < w && y < h {
+                    let c = rgb(x, y);
+                    for i in 0..3 {
+                        sum[i] += c[i];
+                    }
+                    n += 1.0;
+                }
+            }
+            let [r, g, b] = sum.map(|c| c / n);
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            cb[cy * cw + cx] = (128.0 + 224.0 * (b - luma) / 1.8556).round() as u8;
+            cr[cy * cw + cx] = (128.0 + 224.0 * (r - luma) / 1.5748).round() as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_rates_are_reduced_fractions() {
+        assert_eq!(frame_rate(30.0), (30, 1));
+        assert_eq!(frame_rate(12.5), (25, 2));
+        assert_eq!(frame_rate(29.97), (30000, 1001));
+        assert_eq!(frame_rate(23.976), (24000, 1001));
+        assert_eq!(frame_rate(59.94), (60000, 1001));
+        assert_eq!(frame_rate(7.25), (29, 4));
+    }
+
+    fn planes(pixels: &[[u8; 3]], width: u32) -> Vec<u8> {
+        let texels: Vec<u8> = pixels.iter().flat_map(|&[r, g, b]| [r, g, b, 255]).collect();
+        let img = image::RgbaImage::from_raw(width, pixels.len() as u32 / width, texels).unwrap();
+        let mut planes = vec![];
+        to_yuv420(&img, &mut planes);
+        planes
+    }
+
+    #[test]
+    fn bt709_limited_range() {
+        // Y, Cb, Cr of a 1x1 frame
+        let cases = [
+            ([0, 0, 0], [16, 128, 128]),
+            ([255, 255, 255], [235, 128, 128]),
+            ([255, 0, 0], [63, 102, 240]),
+            ([0, 255, 0], [173, 42, 26]),
+            ([0, 0, 255], [32, 240, 118]),
+        ];
+        for (rgb, yuv) in cases {
+            assert_eq!(planes(&[rgb], 1), yuv, "{rgb:?}");
+        }
+    }
+
+    #[test]
+    fn odd_sizes_average_what_is_left_of_a_block() {
+        // 3x1: the first chroma sample averages black and white, the second is red alone
+        let yuv = planes(&[[0, 0, 0], [255, 255, 255], [255, 0, 0]], 3);
+        assert_eq!(yuv, [16, 235, 63, 128, 102, 128, 240]);
+
+        // 3x3 of one colour has 2x2 chroma of that colour
+        let yuv = planes(&[[0, 0, 255]; 9], 3);
+        assert_eq!(yuv, [[32; 9].as_slice(), &[240; 4], &[118; 4]].concat());
+    }
+}